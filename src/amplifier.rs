@@ -0,0 +1,304 @@
+use crate::intcode::{IntcodeVM, ProgMem, RunErr};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::vec::Vec;
+
+/// How the amplifiers are wired together. Amplifiers are numbered in
+/// order, layer by layer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Topology {
+    /// Layers of amplifiers run side by side. The first layer gets an input
+    /// signal of 0; each amplifier in later layers gets the sum of the
+    /// signals from the layer before. The result is the sum from the last.
+    Layers(Vec<usize>),
+    /// Amplifiers in a loop, with the last feeding back into the first
+    /// until they have all halted. The result is the last signal out.
+    Ring(usize),
+}
+
+impl Topology {
+    pub fn chain(n: usize) -> Self {
+        Topology::Layers(vec![1; n])
+    }
+
+    /// One amplifier feeding `width` in parallel, which all feed one more.
+    pub fn fan(width: usize) -> Self {
+        Topology::Layers(vec![1, width, 1])
+    }
+
+    pub fn amps(&self) -> usize {
+        match self {
+            Topology::Layers(layers) => layers.iter().sum(),
+            Topology::Ring(n) => *n,
+        }
+    }
+}
+
+/// Everything one amplifier was given (its phase first) and produced.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AmpTrace {
+    pub amp: usize,
+    pub input: Vec<i64>,
+    pub output: Vec<i64>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Run {
+    pub phases: Vec<i64>,
+    pub signal: i64,
+    pub trace: Vec<AmpTrace>,
+}
+
+// A signal, and how each amplifier that went into it got there.
+type TracedSignal = (i64, Vec<AmpTrace>);
+
+fn no_output(amp: usize) -> RunErr {
+    RunErr::NoOutput(format!("amplifier {amp} produced no signal"))
+}
+
+/// Copies of one program wired up as amplifiers.
+pub struct Circuit {
+    template: IntcodeVM,
+    topology: Topology,
+    pub threads: usize,
+    // Layers only see the signal from the layer before, so everything up
+    // to the end of a layer depends only on the phases given so far, and is
+    // shared between every setting that starts with the same phases. Rings
+    // feed back into the first amplifier, so nothing short of the whole
+    // setting can be reused there, and they aren't cached.
+    prefixes: Mutex<HashMap<Vec<i64>, TracedSignal>>,
+}
+
+impl Circuit {
+    pub fn new(prog: &ProgMem, topology: Topology) -> Self {
+        Self {
+            template: IntcodeVM::with_mem(prog),
+            topology,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            prefixes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn run(&self, phases: &[i64]) -> Result<Run, RunErr> {
+        if phases.len() != self.topology.amps() {
+            return Err(RunErr::InvalidInput(format!(
+                "{} phases for {} amplifiers",
+                phases.len(),
+                self.topology.amps()
+            )));
+        }
+        let (signal, trace) = match &self.topology {
+            Topology::Layers(layers) => self.run_layers(layers, phases)?,
+            Topology::Ring(_) => self.run_ring(phases)?,
+        };
+        Ok(Run {
+            phases: phases.to_vec(),
+            signal,
+            trace,
+        })
+    }
+
+    fn run_amp(&self, phase: i64, signal: i64) -> Result<Vec<i64>, RunErr> {
+        let mut vm = self.template.clone();
+        vm.input_queue.extend([phase, signal]);
+        let mut output = Vec::new();
+        vm.run_with_cb(&mut || None, &mut |v| output.push(v))
+            .map(|_| output)
+    }
+
+    fn run_layers(&self, layers: &[usize], phases: &[i64]) -> Result<TracedSignal, RunErr> {
+        // pick up after the most layers already run with these phases
+        let mut signal = 0;
+        let mut trace = Vec::with_capacity(phases.len());
+        let mut done = 0;
+        let mut end = 0;
+        for (n, width) in layers.iter().enumerate() {
+            end += width;
+            if let Some((s, t)) = self.prefixes.lock().unwrap().get(&phases[..end]) {
+                (signal, trace, done) = (*s, t.clone(), n + 1);
+            }
+        }
+        for width in &layers[done..] {
+            let mut sum = 0;
+            for _ in 0..*width {
+                let amp = trace.len();
+                let phase = phases[amp];
+                let output = self.run_amp(phase, signal)?;
+                sum += output.last().ok_or_else(|| no_output(amp))?;
+                trace.push(AmpTrace {
+                    amp,
+                    input: vec![phase, signal],
+                    output,
+                });
+            }
+            signal = sum;
+            self.prefixes
+                .lock()
+                .unwrap()
+                .insert(phases[..trace.len()].to_vec(), (signal, trace.clone()));
+        }
+        Ok((signal, trace))
+    }
+
+    fn run_ring(&self, phases: &[i64]) -> Result<TracedSignal, RunErr> {
+        let mut vms: Vec<IntcodeVM> = phases
+            .iter()
+            .map(|p| {
+                let mut vm = self.template.clone();
+                vm.input_queue.push_back(*p);
+                vm
+            })
+            .collect();
+        let mut trace: Vec<AmpTrace> = phases
+            .iter()
+            .enumerate()
+            .map(|(amp, p)| AmpTrace {
+                amp,
+                input: vec![*p],
+                output: Vec::new(),
+            })
+            .collect();
+        let mut halted = vec![false; vms.len()];
+        let mut signal = Some(0);
+        while !halted.iter().all(|h| *h) {
+            let mut progress = false;
+            for (amp, vm) in vms.iter_mut().enumerate() {
+                if halted[amp] {
+                    continue;
+                }
+                if let Some(v) = signal.take() {
+                    vm.input_queue.push_back(v);
+                    trace[amp].input.push(v);
+                }
+                let mut output = Vec::new();
+                match vm.run_with_cb(&mut || None, &mut |v| output.push(v)) {
+                    Ok(_) => halted[amp] = true,
+                    Err(RunErr::InputNeeded) => {}
+                    Err(e) => return Err(e),
+                }
+                progress |= halted[amp] || !output.is_empty();
+                signal = output.last().copied();
+                trace[amp].output.extend(output);
+            }
+            // every amplifier is stuck waiting for a signal
+            if !progress {
+                return Err(RunErr::InputNeeded);
+            }
+        }
+        let last = trace.len() - 1;
+        let signal = trace[last].output.last().copied();
+        Ok((signal.ok_or_else(|| no_output(last))?, trace))
+    }
+
+    /// Tries every way of giving the amplifiers distinct phases from
+    /// `alphabet`, spread over the threads, and returns the setting with
+    /// the highest signal (the first one generated, if tied).
+    pub fn optimize(&self, alphabet: &[i64]) -> Result<Option<Run>, RunErr> {
+        let settings = Mutex::new(
+            alphabet
+                .iter()
+                .copied()
+                .permutations(self.topology.amps())
+                .enumerate(),
+        );
+        let best: Mutex<Option<(usize, Run)>> = Mutex::new(None);
+        let error = Mutex::new(None);
+        thread::scope(|s| {
+            for _ in 0..self.threads.max(1) {
+                s.spawn(|| loop {
+                    let Some((idx, phases)) = settings.lock().unwrap().next() else {
+                        break;
+                    };
+                    match self.run(&phases) {
+                        Ok(run) => {
+                            let mut best = best.lock().unwrap();
+                            let better = best.as_ref().is_none_or(|(bidx, brun)| {
+                                (run.signal, *bidx) > (brun.signal, idx)
+                            });
+                            if better {
+                                *best = Some((idx, run));
+                            }
+                        }
+                        Err(e) => {
+                            error.lock().unwrap().get_or_insert(e);
+                        }
+                    }
+                });
+            }
+        });
+        if let Some(e) = error.into_inner().unwrap() {
+            return Err(e);
+        }
+        Ok(best.into_inner().unwrap().map(|(_, run)| run))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_test() {
+        let prog: ProgMem = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"
+            .parse()
+            .unwrap();
+        let circuit = Circuit::new(&prog, Topology::chain(5));
+        let best = circuit.optimize(&[0, 1, 2, 3, 4]).unwrap().unwrap();
+        assert_eq!(best.signal, 43210);
+        assert_eq!(best.phases, [4, 3, 2, 1, 0]);
+        assert_eq!(best.trace[1].input, [3, 4]);
+        assert_eq!(best.trace[1].output, [43]);
+
+        // two-digit phases with more letters than amplifiers
+        let circuit = Circuit::new(&prog, Topology::chain(3));
+        let best = circuit.optimize(&[10, 0, 7, 25]).unwrap().unwrap();
+        assert_eq!((best.signal, best.phases), (2607, vec![25, 10, 7]));
+
+        // one entry per distinct prefix: 4 + 4 * 3 + 4 * 3 * 2
+        assert_eq!(circuit.prefixes.lock().unwrap().len(), 40);
+        assert_eq!(circuit.optimize(&[1, 2]), Ok(None));
+        assert!(circuit.run(&[1, 2]).is_err());
+    }
+
+    #[test]
+    fn ring_test() {
+        let prog: ProgMem =
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5"
+                .parse()
+                .unwrap();
+        let circuit = Circuit::new(&prog, Topology::Ring(5));
+        let best = circuit.optimize(&[5, 6, 7, 8, 9]).unwrap().unwrap();
+        assert_eq!(best.signal, 139629729);
+        assert_eq!(best.phases, [9, 8, 7, 6, 5]);
+        // five loops around the ring
+        assert!(best.trace.iter().all(|t| t.output.len() == 5));
+        assert_eq!(best.trace[4].output.last(), Some(&139629729));
+        assert_eq!(best.trace[0].input[..2], [9, 0]);
+    }
+
+    #[test]
+    fn fan_test() {
+        // outputs phase * 10 + signal
+        let prog: ProgMem = "3,15,3,16,1002,15,10,15,1,15,16,15,4,15,99,0,0"
+            .parse()
+            .unwrap();
+        let circuit = Circuit::new(&prog, Topology::fan(2));
+        let run = circuit.run(&[1, 2, 3, 4]).unwrap();
+        // 10, then 30 and 40 in parallel, then 4 * 10 + 70
+        assert_eq!(run.signal, 110);
+        assert_eq!(run.trace[2].input, [3, 10]);
+        let best = circuit.optimize(&[1, 2, 3, 4]).unwrap().unwrap();
+        // the source's signal reaches the sink twice over
+        assert_eq!(best.signal, 140);
+        assert_eq!(best.phases[0], 4);
+
+        // a program that halts without output
+        let circuit = Circuit::new(&"99".parse().unwrap(), Topology::chain(2));
+        assert_eq!(
+            circuit.run(&[0, 1]),
+            Err(RunErr::NoOutput("amplifier 0 produced no signal".into()))
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::intcode::{IntcodeVM, ProgMem, RunErr, StepResult};
+use std::vec::Vec;
+
+/// Encodes text as Intcode input values, rejecting anything outside 7-bit ASCII.
+pub fn encode(input: &str) -> Result<Vec<i64>, RunErr> {
+    input
+        .chars()
+        .enumerate()
+        .map(|(idx, c)| {
+            if c.is_ascii() {
+                Ok(c as i64)
+            } else {
+                Err(RunErr::InvalidInput(format!(
+                    "non-ASCII character {c:?} at position {idx}"
+                )))
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AsciiOutput {
+    Line(String),
+    Value(i64),
+}
+
+/// Splits a stream of Intcode output values into lines of text
+/// and the non-ASCII values (usually puzzle answers) between them.
+#[derive(Default)]
+pub struct AsciiDecoder {
+    partial: String,
+    output: Vec<AsciiOutput>,
+}
+
+impl AsciiDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, v: i64) {
+        match v {
+            10 => {
+                let line = std::mem::take(&mut self.partial);
+                self.output.push(AsciiOutput::Line(line));
+            }
+            0..=127 => self.partial.push(v as u8 as char),
+            _ => {
+                self.flush();
+                self.output.push(AsciiOutput::Value(v));
+            }
+        }
+    }
+
+    /// Emits any unterminated text as a line of its own.
+    pub fn flush(&mut self) {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.output.push(AsciiOutput::Line(line));
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<AsciiOutput> {
+        std::mem::take(&mut self.output)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Response {
+    pub lines: Vec<String>,
+    pub values: Vec<i64>,
+    pub prompt: Option<String>,
+    pub halted: bool,
+}
+
+impl Response {
+    pub fn text(&self) -> String {
+        self.lines.iter().map(|l| format!("{l}\n")).collect()
+    }
+}
+
+/// Drives an ASCII-speaking Intcode program a line at a time.
+pub struct AsciiTerminal {
+    pub vm: IntcodeVM,
+    prompts: Vec<String>,
+    decoder: AsciiDecoder,
+}
+
+impl AsciiTerminal {
+    pub fn new(vm: IntcodeVM) -> Self {
+        Self {
+            vm,
+            prompts: Vec::new(),
+            decoder: AsciiDecoder::new(),
+        }
+    }
+
+    pub fn with_mem(mem: &ProgMem) -> Self {
+        Self::new(IntcodeVM::with_mem(mem))
+    }
+
+    /// Registers a line (such as "Command?") that marks the end of a response.
+    pub fn add_prompt(&mut self, prompt: &str) {
+        self.prompts.push(prompt.to_owned());
+    }
+
+    pub fn send_line(&mut self, line: &str) -> Result<(), RunErr> {
+        self.vm.ascii_input(line)?;
+        self.vm.input_queue.push_back(10);
+        Ok(())
+    }
+
+    /// Runs until the program halts or wants input that has not been queued.
+    /// If the last line printed is a registered prompt, it is reported in `prompt`.
+    pub fn read_until_prompt(&mut self) -> Result<Response, RunErr> {
+        let mut response = Response::default();
+        loop {
+            match self.vm.step(&mut || None, &mut |v| self.decoder.push(v)) {
+                StepResult::Ok => continue,
+                StepResult::Halt => response.halted = true,
+                StepResult::InputNeeded => {}
+                StepResult::InvalidInstr(err) => return Err(RunErr::InvalidInstr(err)),
+            }
+            break;
+        }
+        self.decoder.flush();
+        for out in self.decoder.take() {
+            match out {
+                AsciiOutput::Line(line) => response.lines.push(line),
+                AsciiOutput::Value(v) => response.values.push(v),
+            }
+        }
+        response.prompt = response
+            .lines
+            .last()
+            .filter(|line| self.prompts.iter().any(|p| p.as_str() == line.trim_end()))
+            .cloned();
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_test() {
+        assert_eq!(encode("Hi\n"), Ok(vec![72, 105, 10]));
+        assert!(matches!(encode("caf\u{e9}"), Err(RunErr::InvalidInput(_))));
+
+        let mut dec = AsciiDecoder::new();
+        [79, 75, 10, 1234, 72].iter().for_each(|v| dec.push(*v));
+        dec.flush();
+        assert_eq!(
+            dec.take(),
+            [
+                AsciiOutput::Line("OK".into()),
+                AsciiOutput::Value(1234),
+                AsciiOutput::Line("H".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn terminal_test() {
+        // prints "OK?", then echoes input back until it reads a 0
+        let prog: ProgMem = "104,79,104,75,104,63,104,10,3,100,4,100,1005,100,8,104,9999,99"
+            .parse()
+            .unwrap();
+        let mut term = AsciiTerminal::with_mem(&prog);
+        term.add_prompt("OK?");
+        let resp = term.read_until_prompt().unwrap();
+        assert_eq!(resp.lines, ["OK?"]);
+        assert_eq!(resp.prompt.as_deref(), Some("OK?"));
+        assert!(!resp.halted);
+
+        term.send_line("hello").unwrap();
+        let resp = term.read_until_prompt().unwrap();
+        assert_eq!(resp.text(), "hello\n");
+        assert!(!resp.halted);
+
+        assert!(term.send_line("\u{2603}").is_err());
+        assert!(term.vm.input_queue.is_empty());
+        term.vm.input_queue.push_back(0);
+        let resp = term.read_until_prompt().unwrap();
+        assert_eq!(resp.values, [9999]);
+        assert!(resp.halted);
+    }
+}
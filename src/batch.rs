@@ -0,0 +1,146 @@
+use crate::intcode::{IntcodeVM, ProgMem, RunErr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::vec::Vec;
+
+/// One run of a batch: memory cells to overwrite before starting,
+/// and the values to queue as input.
+#[derive(Clone, Debug, Default)]
+pub struct Job {
+    pub patches: Vec<(usize, i64)>,
+    pub input: Vec<i64>,
+}
+
+impl Job {
+    pub fn new(patches: Vec<(usize, i64)>, input: Vec<i64>) -> Self {
+        Self { patches, input }
+    }
+}
+
+/// The final VM state and everything a job's program output. `status` is
+/// `Err(RunErr::InputNeeded)` if the program wanted more input than given.
+#[derive(Clone)]
+pub struct Outcome {
+    pub vm: IntcodeVM,
+    pub output: Vec<i64>,
+    pub status: Result<(), RunErr>,
+}
+
+/// Runs the same program many times, spread over a pool of threads.
+pub struct Batch {
+    template: IntcodeVM,
+    pub threads: usize,
+}
+
+impl Batch {
+    pub fn new(prog: &ProgMem) -> Self {
+        Self {
+            template: IntcodeVM::with_mem(prog),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    pub fn run_one(&self, job: &Job) -> Outcome {
+        let mut vm = self.template.clone();
+        for (addr, val) in &job.patches {
+            if *addr >= vm.mem.len() {
+                vm.mem.resize(addr + 1, 0);
+            }
+            vm.mem[*addr] = *val;
+        }
+        vm.input_queue.extend(&job.input);
+        let mut output = Vec::new();
+        let status = vm.run_with_cb(&mut || None, &mut |v| output.push(v));
+        Outcome { vm, output, status }
+    }
+
+    /// Runs every job, returning the outcomes in the order the jobs were generated.
+    pub fn run_all<I>(&self, jobs: I) -> Vec<Outcome>
+    where
+        I: IntoIterator<Item = Job>,
+        I::IntoIter: Send,
+    {
+        let mut results = self.execute(jobs, &|_: &Outcome| false);
+        results.sort_by_key(|(idx, _)| *idx);
+        results.into_iter().map(|(_, outcome)| outcome).collect()
+    }
+
+    /// Returns the earliest-generated job whose outcome satisfies `pred`,
+    /// along with its index. Jobs generated after a match are not started.
+    pub fn find_first<I, P>(&self, jobs: I, pred: P) -> Option<(usize, Outcome)>
+    where
+        I: IntoIterator<Item = Job>,
+        I::IntoIter: Send,
+        P: Fn(&Outcome) -> bool + Sync,
+    {
+        self.execute(jobs, &pred)
+            .into_iter()
+            .filter(|(_, outcome)| pred(outcome))
+            .min_by_key(|(idx, _)| *idx)
+    }
+
+    fn execute<I, P>(&self, jobs: I, stop: &P) -> Vec<(usize, Outcome)>
+    where
+        I: IntoIterator<Item = Job>,
+        I::IntoIter: Send,
+        P: Fn(&Outcome) -> bool + Sync,
+    {
+        let queue = Mutex::new(jobs.into_iter().enumerate());
+        let first_stop = AtomicUsize::new(usize::MAX);
+        let results = Mutex::new(Vec::new());
+        thread::scope(|s| {
+            for _ in 0..self.threads.max(1) {
+                s.spawn(|| loop {
+                    // Jobs are taken in order, so once a stop is found every job
+                    // before it has already been taken and will still complete.
+                    let Some((idx, job)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    if idx > first_stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let outcome = self.run_one(&job);
+                    if stop(&outcome) {
+                        first_stop.fetch_min(idx, Ordering::Relaxed);
+                    }
+                    results.lock().unwrap().push((idx, outcome));
+                });
+            }
+        });
+        results.into_inner().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_test() {
+        // outputs 1 if the input is 8, 0 otherwise
+        let prog: ProgMem = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let batch = Batch::new(&prog);
+        let outcomes = batch.run_all((0..20).map(|v| Job::new(vec![], vec![v])));
+        let outputs: Vec<i64> = outcomes.iter().map(|o| o.output[0]).collect();
+        assert_eq!(
+            outputs,
+            (0..20).map(|v| i64::from(v == 8)).collect::<Vec<_>>()
+        );
+
+        let (idx, outcome) = batch
+            .find_first((0..).map(|v| Job::new(vec![], vec![v])), |o| {
+                o.output == [1]
+            })
+            .unwrap();
+        assert_eq!(idx, 8);
+        assert_eq!(outcome.status, Ok(()));
+
+        let outcome = batch.run_one(&Job::default());
+        assert_eq!(outcome.status, Err(RunErr::InputNeeded));
+
+        // patching the comparison constant
+        let outcome = batch.run_one(&Job::new(vec![(10, 3)], vec![3]));
+        assert_eq!(outcome.output, [1]);
+    }
+}
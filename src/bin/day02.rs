@@ -2,6 +2,7 @@ use itertools::Itertools;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
+use advent2019::batch::{Batch, Job};
 use advent2019::intcode::{IntcodeVM, ProgMem};
 
 fn part1(input: &ProgMem) -> i64 {
@@ -13,17 +14,16 @@ fn part1(input: &ProgMem) -> i64 {
 }
 
 fn part2(input: &ProgMem) -> i64 {
-    (0..=99)
-        .cartesian_product(0..=99)
-        .map(|(n, v)| {
-            let mut vm = IntcodeVM::with_mem(input);
-            vm.mem[1] = n;
-            vm.mem[2] = v;
-            vm.run().unwrap();
-            (n, v, vm.mem[0])
+    let inputs: Vec<(i64, i64)> = (0..=99).cartesian_product(0..=99).collect();
+    let jobs = inputs
+        .iter()
+        .map(|(n, v)| Job::new(vec![(1, *n), (2, *v)], vec![]));
+    Batch::new(input)
+        .find_first(jobs, |o| o.vm.mem[0] == 19690720)
+        .map(|(idx, _)| {
+            let (noun, verb) = inputs[idx];
+            noun * 100 + verb
         })
-        .find(|(_, _, res)| *res == 19690720)
-        .map(|(n, v, _)| n * 100 + v)
         .unwrap()
 }
 
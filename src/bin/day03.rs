@@ -1,4 +1,6 @@
-use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
+use std::fs;
 use std::ops::RangeInclusive;
 use std::str::FromStr;
 use std::vec::Vec;
@@ -41,7 +43,7 @@ impl FromStr for WirePath {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Debug)]
 struct Segment {
     a: Coord2D,
     b: Coord2D,
@@ -69,75 +71,317 @@ impl Segment {
     fn len(&self) -> i64 {
         self.a.mdist_to(&self.b)
     }
-    // Perpendicular intersection (returns None for co-linear overlaps)
-    fn p_intersect(&self, other: &Self) -> Option<Coord2D> {
-        if self.a.x == self.b.x
-            && other.a.y == other.b.y
-            && self.y_range().contains(&other.a.y)
-            && other.x_range().contains(&self.a.x)
-        {
-            Some(Coord2D::new(self.a.x, other.a.y))
-        } else if self.a.y == self.b.y
-            && other.a.x == other.b.x
-            && self.x_range().contains(&other.a.x)
-            && other.y_range().contains(&self.a.y)
-        {
-            Some(Coord2D::new(other.a.x, self.a.y))
+    fn is_horizontal(&self) -> bool {
+        self.a.y == self.b.y && self.a.x != self.b.x
+    }
+    fn is_vertical(&self) -> bool {
+        self.a.x == self.b.x && self.a.y != self.b.y
+    }
+}
+
+/// A segment of one wire, with the signal delay (steps taken along the
+/// wire) at its start.
+#[derive(Copy, Clone, Debug)]
+struct WireSeg {
+    wire: usize,
+    seg: Segment,
+    delay: i64,
+}
+impl WireSeg {
+    fn delay_at(&self, c: Coord2D) -> i64 {
+        self.delay + self.seg.a.mdist_to(&c)
+    }
+}
+
+/// Where segments of two different wires meet: a single point for a
+/// perpendicular crossing, or the run of points `from..=to` for colinear
+/// segments that overlap.
+#[derive(Copy, Clone, Debug)]
+struct Intersection {
+    from: Coord2D,
+    to: Coord2D,
+    segs: [WireSeg; 2],
+}
+impl Intersection {
+    fn contains(&self, c: Coord2D) -> bool {
+        (self.from.x..=self.to.x).contains(&c.x) && (self.from.y..=self.to.y).contains(&c.y)
+    }
+    /// The signal delay of each of the two wires at `c`.
+    fn delays_at(&self, c: Coord2D) -> [i64; 2] {
+        [self.segs[0].delay_at(c), self.segs[1].delay_at(c)]
+    }
+    // Distance from the origin and delay are both piecewise linear along the
+    // run, so each is minimized at an end, the point nearest the origin, or
+    // right beside the origin (which doesn't count as a crossing).
+    fn candidates(&self) -> impl Iterator<Item = Coord2D> + '_ {
+        let origin = Coord2D::new(0, 0);
+        let nearest = Coord2D::new(
+            0.clamp(self.from.x, self.to.x),
+            0.clamp(self.from.y, self.to.y),
+        );
+        let step = if self.from.x == self.to.x {
+            Coord2D::new(0, 1)
         } else {
-            None
+            Coord2D::new(1, 0)
+        };
+        [self.from, self.to, nearest, nearest + step, nearest - step]
+            .into_iter()
+            .filter(move |c| *c != origin && self.contains(*c))
+    }
+    fn closest(&self) -> Option<(i64, Coord2D)> {
+        let origin = Coord2D::new(0, 0);
+        self.candidates()
+            .map(|c| (c.mdist_to(&origin), c))
+            .min_by_key(|(d, _)| *d)
+    }
+    fn min_delay(&self) -> Option<(i64, Coord2D)> {
+        self.candidates()
+            .map(|c| (self.delays_at(c).iter().sum(), c))
+            .min_by_key(|(d, _)| *d)
+    }
+}
+
+struct Circuit {
+    segs: Vec<WireSeg>,
+}
+impl Circuit {
+    fn new(wires: &[WirePath]) -> Self {
+        let mut segs = Vec::new();
+        for (wire, wp) in wires.iter().enumerate() {
+            let mut delay = 0;
+            for seg in Segment::from_wirepath(wp) {
+                segs.push(WireSeg { wire, seg, delay });
+                delay += seg.len();
+            }
+        }
+        Self { segs }
+    }
+
+    fn meet(&self, i: usize, j: usize, from: Coord2D, to: Coord2D) -> Option<Intersection> {
+        let segs = [self.segs[i], self.segs[j]];
+        (segs[0].wire != segs[1].wire).then_some(Intersection { from, to, segs })
+    }
+
+    /// Every intersection between segments of different wires, found by
+    /// sweeping across x for perpendicular crossings and along each line
+    /// for colinear overlaps.
+    fn intersections(&self) -> Vec<Intersection> {
+        let mut found = self.crossings();
+        found.extend(self.overlaps(|s| s.is_horizontal(), |c| (c.y, c.x)));
+        found.extend(self.overlaps(|s| s.is_vertical(), |c| (c.x, c.y)));
+        found
+    }
+
+    fn crossings(&self) -> Vec<Intersection> {
+        const INSERT: u8 = 0;
+        const QUERY: u8 = 1;
+        const REMOVE: u8 = 2;
+        let mut events = Vec::new();
+        for (idx, ws) in self.segs.iter().enumerate() {
+            let xr = ws.seg.x_range();
+            if ws.seg.is_horizontal() {
+                events.push((*xr.start(), INSERT, idx));
+                events.push((*xr.end(), REMOVE, idx));
+            } else if ws.seg.is_vertical() {
+                events.push((ws.seg.a.x, QUERY, idx));
+            }
+        }
+        events.sort_unstable();
+
+        let mut active = BTreeSet::new();
+        let mut found = Vec::new();
+        for (x, kind, idx) in events {
+            let y = self.segs[idx].seg.a.y;
+            match kind {
+                INSERT => {
+                    active.insert((y, idx));
+                }
+                REMOVE => {
+                    active.remove(&(y, idx));
+                }
+                _ => {
+                    let yr = self.segs[idx].seg.y_range();
+                    for (hy, h) in active.range((*yr.start(), 0)..=(*yr.end(), usize::MAX)) {
+                        let c = Coord2D::new(x, *hy);
+                        found.extend(self.meet(*h, idx, c, c));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    // `key` maps a point to (line, position along the line).
+    fn overlaps<S, K>(&self, select: S, key: K) -> Vec<Intersection>
+    where
+        S: Fn(&Segment) -> bool,
+        K: Fn(Coord2D) -> (i64, i64),
+    {
+        let mut spans: Vec<(i64, i64, i64, usize)> = self
+            .segs
+            .iter()
+            .enumerate()
+            .filter(|(_, ws)| select(&ws.seg))
+            .map(|(idx, ws)| {
+                let (line, a) = key(ws.seg.a);
+                let (_, b) = key(ws.seg.b);
+                (line, a.min(b), a.max(b), idx)
+            })
+            .collect();
+        spans.sort_unstable();
+
+        let mut found = Vec::new();
+        let mut active: BinaryHeap<Reverse<(i64, usize)>> = BinaryHeap::new();
+        let mut line = None;
+        for (l, lo, hi, idx) in spans {
+            if line != Some(l) {
+                active.clear();
+                line = Some(l);
+            }
+            while active.peek().is_some_and(|Reverse((end, _))| *end < lo) {
+                active.pop();
+            }
+            // every remaining span started no later and ends no earlier than `lo`
+            for Reverse((end, other)) in active.iter() {
+                let to = (*end).min(hi);
+                let (from, to) = if self.segs[idx].seg.a.x == self.segs[idx].seg.b.x {
+                    (Coord2D::new(l, lo), Coord2D::new(l, to))
+                } else {
+                    (Coord2D::new(lo, l), Coord2D::new(to, l))
+                };
+                found.extend(self.meet(*other, idx, from, to));
+            }
+            active.push(Reverse((hi, idx)));
         }
+        found
     }
 }
 
-fn part1(input: &[WirePath]) -> i64 {
-    let wire_a = Segment::from_wirepath(&input[0]);
-    let wire_b = Segment::from_wirepath(&input[1]);
-    let origin = Coord2D::new(0, 0);
-    wire_b
+fn closest(found: &[Intersection]) -> Option<(i64, Coord2D)> {
+    found
         .iter()
-        .cartesian_product(wire_a)
-        .filter_map(|(b, a)| a.p_intersect(b))
-        .filter(|c| *c != origin)
-        .map(|c| c.mdist_to(&origin))
-        .min()
-        .unwrap()
+        .filter_map(|i| i.closest())
+        .min_by_key(|(d, _)| *d)
 }
 
-fn part2(input: &[WirePath]) -> i64 {
-    let wire_a = Segment::from_wirepath(&input[0]);
-    let wire_b = Segment::from_wirepath(&input[1]);
-    let mut a_len = 0;
-    let mut b_len = 0;
-    wire_b
+fn fastest(found: &[Intersection]) -> Option<(i64, Coord2D)> {
+    found
         .iter()
-        .cartesian_product(wire_a.iter().enumerate())
-        .filter_map(|(b, (a_idx, a))| {
-            if a_idx == 0 {
-                a_len = 0;
-            }
-            let r = a
-                .p_intersect(b)
-                .map(|c| a_len + b_len + a.a.mdist_to(&c) + b.a.mdist_to(&c));
-            a_len += a.len();
-            if a_idx == wire_a.len() - 1 {
-                b_len += b.len();
-            }
-            r
-        })
-        .filter(|c| *c != 0)
-        .min()
-        .unwrap()
+        .filter_map(|i| i.min_delay())
+        .min_by_key(|(d, _)| *d)
+}
+
+const WIRE_COLOURS: &[&str] = &[
+    "#d62728", "#1f77b4", "#2ca02c", "#9467bd", "#ff7f0e", "#17becf", "#8c564b", "#e377c2",
+];
+
+/// Draws each wire in its own colour, with a dot at every crossing (and a
+/// thick stroke along colinear overlaps), a ring around the origin, and
+/// labels on the closest and lowest-delay crossings.
+fn to_svg(circuit: &Circuit, found: &[Intersection]) -> String {
+    let points = circuit.segs.iter().flat_map(|ws| [ws.seg.a, ws.seg.b]);
+    let (mut xmin, mut xmax, mut ymin, mut ymax) = (0, 0, 0, 0);
+    for p in points {
+        xmin = xmin.min(p.x);
+        xmax = xmax.max(p.x);
+        ymin = ymin.min(p.y);
+        ymax = ymax.max(p.y);
+    }
+    let span = (xmax - xmin).max(ymax - ymin).max(1);
+    let margin = span / 20 + 1;
+    let r = span as f64 / 300.0;
+    let font = span as f64 / 60.0;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"1000\" height=\"{}\">\n",
+        xmin - margin,
+        ymin - margin,
+        xmax - xmin + 2 * margin,
+        ymax - ymin + 2 * margin,
+        1000 * (ymax - ymin + 2 * margin) / (xmax - xmin + 2 * margin),
+    );
+    svg.push_str("<rect x=\"-100%\" y=\"-100%\" width=\"300%\" height=\"300%\" fill=\"white\"/>\n");
+    let mut segs = circuit.segs.iter().peekable();
+    while let Some(first) = segs.next() {
+        let mut path = format!("M{},{}", first.seg.a.x, first.seg.a.y);
+        path.push_str(&format!(" L{},{}", first.seg.b.x, first.seg.b.y));
+        while let Some(ws) = segs.next_if(|ws| ws.wire == first.wire) {
+            path.push_str(&format!(" L{},{}", ws.seg.b.x, ws.seg.b.y));
+        }
+        svg.push_str(&format!(
+            "<path d=\"{path}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" vector-effect=\"non-scaling-stroke\" stroke-opacity=\"0.8\"/>\n",
+            WIRE_COLOURS[first.wire % WIRE_COLOURS.len()]
+        ));
+    }
+    for i in found {
+        if i.from != i.to {
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-width=\"5\" vector-effect=\"non-scaling-stroke\"/>\n",
+                i.from.x, i.from.y, i.to.x, i.to.y
+            ));
+        }
+        for c in [i.from, i.to]
+            .iter()
+            .take(if i.from == i.to { 1 } else { 2 })
+        {
+            svg.push_str(&format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{r}\" fill=\"black\"/>\n",
+                c.x, c.y
+            ));
+        }
+    }
+    svg.push_str(&format!(
+        "<circle cx=\"0\" cy=\"0\" r=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"2\" vector-effect=\"non-scaling-stroke\"/>\n",
+        r * 3.0
+    ));
+    let labels = [
+        ("closest", closest(found)),
+        ("lowest delay", fastest(found)),
+    ];
+    for (n, (name, best)) in labels.iter().enumerate() {
+        if let Some((value, c)) = best {
+            svg.push_str(&format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"gold\" stroke=\"black\" vector-effect=\"non-scaling-stroke\"/>\n",
+                c.x,
+                c.y,
+                r * 2.0
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{font}\" font-family=\"sans-serif\">{name}: {value} at ({}, {})</text>\n",
+                c.x as f64 + r * 3.0,
+                c.y as f64 - r * 3.0 + n as f64 * font * 1.2,
+                c.x,
+                c.y
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn part1(input: &[WirePath]) -> i64 {
+    closest(&Circuit::new(input).intersections()).unwrap().0
+}
+
+fn part2(input: &[WirePath]) -> i64 {
+    fastest(&Circuit::new(input).intersections()).unwrap().0
 }
 
 fn main() {
     let input: Vec<WirePath> = read_input();
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
+    // pass a filename to also draw the wires there
+    if let Some(path) = std::env::args().nth(1) {
+        let circuit = Circuit::new(&input);
+        fs::write(path, to_svg(&circuit, &circuit.intersections())).unwrap();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
     use ya_advent_lib::read::test_input;
 
     #[test]
@@ -155,4 +399,108 @@ mod tests {
         assert_eq!(part1(&input), 135);
         assert_eq!(part2(&input), 410);
     }
+
+    // Walks every point of every wire, noting the first delay at which each
+    // wire reaches it.
+    fn brute_force(input: &[WirePath]) -> (i64, i64) {
+        let mut visits: HashMap<(i64, i64), Vec<i64>> = HashMap::new();
+        for (wire, wp) in input.iter().enumerate() {
+            let mut pos = Coord2D::new(0, 0);
+            let mut delay = 0;
+            for seg in &wp.0 {
+                for _ in 0..seg.steps {
+                    pos += seg.dir;
+                    delay += 1;
+                    let v = visits
+                        .entry(pos.into())
+                        .or_insert_with(|| vec![i64::MAX; input.len()]);
+                    v[wire] = v[wire].min(delay);
+                }
+            }
+        }
+        let mut closest = i64::MAX;
+        let mut fastest = i64::MAX;
+        for ((x, y), mut delays) in visits {
+            delays.sort();
+            if delays[1] < i64::MAX && (x, y) != (0, 0) {
+                closest = closest.min(x.abs() + y.abs());
+                fastest = fastest.min(delays[0] + delays[1]);
+            }
+        }
+        (closest, fastest)
+    }
+
+    #[test]
+    fn intersections_test() {
+        // colinear overlaps only, one running against the other
+        let input: Vec<WirePath> = test_input("R10\nU1,R3,D1,R5,L2\n");
+        let found = Circuit::new(&input).intersections();
+        assert_eq!(found.len(), 4);
+        let overlap = found.iter().find(|i| i.from != i.to).unwrap();
+        assert_eq!(
+            (overlap.from, overlap.to),
+            (Coord2D::new(3, 0), Coord2D::new(8, 0))
+        );
+        assert_eq!(overlap.delays_at(Coord2D::new(6, 0)), [6, 8]);
+        assert_eq!(part1(&input), 3);
+        assert_eq!(part2(&input), 8);
+
+        // three wires that wander over each other
+        let mut rng: u64 = 12345;
+        let mut next = |n: u64| {
+            rng = rng
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (rng >> 33) % n
+        };
+        for _ in 0..20 {
+            let wires: Vec<String> = (0..3)
+                .map(|_| {
+                    (0..30)
+                        .map(|_| format!("{}{}", ['U', 'D', 'L', 'R'][next(4) as usize], next(8)))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect();
+            let input: Vec<WirePath> = test_input(&wires.join("\n"));
+            let (closest, fastest) = brute_force(&input);
+            if closest < i64::MAX {
+                assert_eq!((part1(&input), part2(&input)), (closest, fastest));
+            }
+        }
+
+        // two long staircases touching at every corner
+        let input = [
+            WirePath(
+                ["R2", "U2"]
+                    .repeat(100_000)
+                    .iter()
+                    .map(|s| s.parse().unwrap())
+                    .collect(),
+            ),
+            WirePath(
+                ["U2", "R2"]
+                    .repeat(100_000)
+                    .iter()
+                    .map(|s| s.parse().unwrap())
+                    .collect(),
+            ),
+        ];
+        assert_eq!(part1(&input), 4);
+        assert_eq!(part2(&input), 8);
+    }
+
+    #[test]
+    fn svg_test() {
+        let input: Vec<WirePath> = test_input("R8,U5,L5,D3\nU7,R6,D4,L4\n");
+        let circuit = Circuit::new(&input);
+        let svg = to_svg(&circuit, &circuit.intersections());
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<path ").count(), 2);
+        assert!(svg.contains("d=\"M0,0 L8,0 L8,-5 L3,-5 L3,-2\""));
+        assert!(svg.contains("stroke=\"#1f77b4\""));
+        assert!(svg.contains(">closest: 6 at (3, -3)</text>"));
+        assert!(svg.contains(">lowest delay: 30 at (6, -5)</text>"));
+    }
 }
@@ -1,39 +1,323 @@
-use itertools::Itertools;
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::vec::Vec;
 use ya_advent_lib::range::range_from_str;
 use ya_advent_lib::read::read_input;
 
-fn valid_password(pw: usize) -> bool {
-    let pws = format!("{pw:06}");
-    pws.chars().tuple_windows().all(|(a, b)| a <= b)
-        && pws.chars().tuple_windows().any(|(a, b)| a == b)
+// Rule states are packed into a u32: the previous digit in the low four
+// bits (NONE before the first), the length of the current run of that
+// digit above it, and a "satisfied" flag at the top.
+const NONE: u32 = 10;
+const SATISFIED: u32 = 1 << 31;
+
+fn pack(last: u32, run: u32, satisfied: bool) -> u32 {
+    last | (run << 4) | if satisfied { SATISFIED } else { 0 }
 }
 
-fn valid_password_p2(pw: usize) -> bool {
-    let pws = format!("{pw:06}");
-    pws.chars().tuple_windows().all(|(a, b)| a <= b)
-        && pws.chars().dedup_with_count().any(|(c, _)| c == 2)
+fn unpack(state: u32) -> (u32, u32, bool) {
+    (
+        state & 0xf,
+        (state & !SATISFIED) >> 4,
+        state & SATISFIED != 0,
+    )
 }
 
-fn part1(input: &str) -> usize {
-    let range = range_from_str(input, true).unwrap();
-    range.filter(|v| valid_password(*v)).count()
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Rule {
+    /// Digits never decrease from left to right
+    NonDecreasing,
+    /// Some digit appears at least twice in a row
+    HasPair,
+    /// Some run of a repeated digit is exactly this long
+    ExactRun(u32),
+    /// None of these digits appear
+    Forbidden(Vec<u8>),
+}
+
+impl Rule {
+    fn start(&self) -> u32 {
+        pack(NONE, 0, false)
+    }
+
+    /// The state after reading digit `d`, or None if the number can no
+    /// longer match.
+    fn step(&self, state: u32, d: u32) -> Option<u32> {
+        let (last, run, satisfied) = unpack(state);
+        let run_after = if d == last { run + 1 } else { 1 };
+        match self {
+            Rule::NonDecreasing => (last == NONE || d >= last).then_some(pack(d, 0, false)),
+            Rule::HasPair => Some(pack(d, run_after.min(2), satisfied || run_after >= 2)),
+            Rule::ExactRun(n) => {
+                let ended = d != last && run == *n;
+                // runs longer than n are all alike, so cap the count there
+                Some(pack(d, run_after.min(n + 1), satisfied || ended))
+            }
+            Rule::Forbidden(digits) => (!digits.contains(&(d as u8))).then_some(state),
+        }
+    }
+
+    fn accept(&self, state: u32) -> bool {
+        let (_, run, satisfied) = unpack(state);
+        match self {
+            Rule::HasPair => satisfied,
+            Rule::ExactRun(n) => satisfied || run == *n,
+            _ => true,
+        }
+    }
+}
+
+/// A set of rules that a password must satisfy all of. Numbers are taken
+/// without leading zeros.
+#[derive(Clone, Debug, Default)]
+struct Rules(Vec<Rule>);
+
+impl FromStr for Rules {
+    type Err = String;
+    /// Parses a comma-separated list such as `nondecreasing,pair,run=3,forbid=07`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|r| !r.is_empty())
+            .map(|r| match r.split_once('=') {
+                None if r == "nondecreasing" => Ok(Rule::NonDecreasing),
+                None if r == "pair" => Ok(Rule::HasPair),
+                Some(("run", n)) => n
+                    .parse()
+                    .map(Rule::ExactRun)
+                    .map_err(|_| format!("Invalid run length {n:?}")),
+                Some(("forbid", ds)) => ds
+                    .chars()
+                    .map(|c| c.to_digit(10).map(|d| d as u8))
+                    .collect::<Option<Vec<u8>>>()
+                    .map(Rule::Forbidden)
+                    .ok_or_else(|| format!("Invalid digits {ds:?}")),
+                _ => Err(format!("Unknown rule {r:?}")),
+            })
+            .collect::<Result<Vec<Rule>, String>>()
+            .map(Rules)
+    }
+}
+
+impl Rules {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with(mut self, rule: Rule) -> Self {
+        self.0.push(rule);
+        self
+    }
+
+    fn count(&self, lo: usize, hi: usize) -> u64 {
+        if lo > hi {
+            return 0;
+        }
+        Search::new(self, lo, hi).count(0, true, true, false, &self.start())
+    }
+
+    /// Lists the matching numbers in order, only visiting branches of the
+    /// digit tree that contain at least one.
+    fn matching(&self, lo: usize, hi: usize) -> Vec<usize> {
+        let mut out = Vec::new();
+        if lo <= hi {
+            Search::new(self, lo, hi).collect(0, true, true, false, &self.start(), 0, &mut out);
+        }
+        out
+    }
+
+    fn start(&self) -> Vec<u32> {
+        self.0.iter().map(|r| r.start()).collect()
+    }
+}
+
+// Walks the digits of numbers in lo..=hi from the most significant. While a
+// prefix matches a bound, only digits within that bound may follow; once
+// it's clear of both, the count depends only on the position and the rule
+// states, so those are memoized.
+struct Search<'a> {
+    rules: &'a Rules,
+    lo: Vec<u32>,
+    hi: Vec<u32>,
+    memo: HashMap<(usize, bool, Vec<u32>), u64>,
+}
+
+impl<'a> Search<'a> {
+    fn new(rules: &'a Rules, lo: usize, hi: usize) -> Self {
+        let hi: Vec<u32> = hi
+            .to_string()
+            .chars()
+            .map(|c| c.to_digit(10).unwrap())
+            .collect();
+        let lo: Vec<u32> = format!("{lo:0width$}", width = hi.len())
+            .chars()
+            .map(|c| c.to_digit(10).unwrap())
+            .collect();
+        Self {
+            rules,
+            lo,
+            hi,
+            memo: HashMap::new(),
+        }
+    }
+
+    fn digits(&self, pos: usize, tight_lo: bool, tight_hi: bool) -> std::ops::RangeInclusive<u32> {
+        let first = if tight_lo { self.lo[pos] } else { 0 };
+        let last = if tight_hi { self.hi[pos] } else { 9 };
+        first..=last
+    }
+
+    // The state after appending `d`, and whether the number has started.
+    // Leading zeros are skipped, except for the number 0 itself.
+    fn advance(
+        &self,
+        pos: usize,
+        started: bool,
+        states: &[u32],
+        d: u32,
+    ) -> Option<(bool, Vec<u32>)> {
+        if !started && d == 0 && pos + 1 < self.hi.len() {
+            return Some((false, states.to_vec()));
+        }
+        let next = self
+            .rules
+            .0
+            .iter()
+            .zip(states)
+            .map(|(rule, s)| rule.step(*s, d))
+            .collect::<Option<Vec<u32>>>()?;
+        Some((true, next))
+    }
+
+    fn count(
+        &mut self,
+        pos: usize,
+        tight_lo: bool,
+        tight_hi: bool,
+        started: bool,
+        states: &[u32],
+    ) -> u64 {
+        if pos == self.hi.len() {
+            return u64::from(self.rules.0.iter().zip(states).all(|(r, s)| r.accept(*s)));
+        }
+        let key = (pos, started, states.to_vec());
+        let free = !tight_lo && !tight_hi;
+        if free {
+            if let Some(n) = self.memo.get(&key) {
+                return *n;
+            }
+        }
+        let mut total = 0;
+        for d in self.digits(pos, tight_lo, tight_hi) {
+            if let Some((started, next)) = self.advance(pos, started, states, d) {
+                total += self.count(
+                    pos + 1,
+                    tight_lo && d == self.lo[pos],
+                    tight_hi && d == self.hi[pos],
+                    started,
+                    &next,
+                );
+            }
+        }
+        if free {
+            self.memo.insert(key, total);
+        }
+        total
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn collect(
+        &mut self,
+        pos: usize,
+        tight_lo: bool,
+        tight_hi: bool,
+        started: bool,
+        states: &[u32],
+        value: usize,
+        out: &mut Vec<usize>,
+    ) {
+        if self.count(pos, tight_lo, tight_hi, started, states) == 0 {
+            return;
+        }
+        if pos == self.hi.len() {
+            out.push(value);
+            return;
+        }
+        for d in self.digits(pos, tight_lo, tight_hi) {
+            if let Some((started, next)) = self.advance(pos, started, states, d) {
+                self.collect(
+                    pos + 1,
+                    tight_lo && d == self.lo[pos],
+                    tight_hi && d == self.hi[pos],
+                    started,
+                    &next,
+                    value * 10 + d as usize,
+                    out,
+                );
+            }
+        }
+    }
+}
+
+fn part1_rules() -> Rules {
+    Rules::new().with(Rule::NonDecreasing).with(Rule::HasPair)
 }
 
-fn part2(input: &str) -> usize {
+fn part2_rules() -> Rules {
+    Rules::new()
+        .with(Rule::NonDecreasing)
+        .with(Rule::ExactRun(2))
+}
+
+fn count_in(input: &str, rules: &Rules) -> u64 {
     let range = range_from_str(input, true).unwrap();
-    range.filter(|v| valid_password_p2(*v)).count()
+    match (range.clone().min(), range.max()) {
+        (Some(lo), Some(hi)) => rules.count(lo, hi),
+        _ => 0,
+    }
 }
 
+fn part1(input: &str) -> u64 {
+    count_in(input, &part1_rules())
+}
+
+fn part2(input: &str) -> u64 {
+    count_in(input, &part2_rules())
+}
+
+// usage: day04 [-l] [rules...]
+//
+// Each extra argument is a rule set (see `Rules::from_str`) to count the
+// passwords in the input range for; with -l the passwords are listed too.
 fn main() {
     let input: Vec<String> = read_input();
     println!("Part 1: {}", part1(&input[0]));
     println!("Part 2: {}", part2(&input[0]));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let list = args.iter().any(|a| a == "-l");
+    let range = range_from_str(&input[0], true).unwrap();
+    let (lo, hi) = (range.clone().min().unwrap_or(1), range.max().unwrap_or(0));
+    for spec in args.iter().filter(|a| *a != "-l") {
+        let rules: Rules = spec.parse().unwrap();
+        println!("{spec}: {}", rules.count(lo, hi));
+        if list {
+            for pw in rules.matching(lo, hi) {
+                println!("  {pw}");
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
+
+    fn valid_password(pw: usize) -> bool {
+        part1_rules().count(pw, pw) == 1
+    }
+
+    fn valid_password_p2(pw: usize) -> bool {
+        part2_rules().count(pw, pw) == 1
+    }
 
     #[test]
     fn day04_test() {
@@ -50,4 +334,50 @@ mod tests {
         assert!(valid_password(111122));
         assert!(valid_password_p2(111122));
     }
+
+    // Checks each number the slow way
+    fn brute_force(n: usize, rules: &Rules) -> bool {
+        let s = n.to_string();
+        rules.0.iter().all(|r| match r {
+            Rule::NonDecreasing => s.chars().tuple_windows().all(|(a, b)| a <= b),
+            Rule::HasPair => s.chars().dedup_with_count().any(|(c, _)| c >= 2),
+            Rule::ExactRun(k) => s.chars().dedup_with_count().any(|(c, _)| c == *k as usize),
+            Rule::Forbidden(ds) => s.bytes().all(|b| !ds.contains(&(b - b'0'))),
+        })
+    }
+
+    #[test]
+    fn rules_test() {
+        let rule_sets = [
+            part1_rules(),
+            part2_rules(),
+            Rules::new().with(Rule::ExactRun(3)),
+            "pair,forbid=07".parse().unwrap(),
+            Rules::new(),
+        ];
+        for rules in &rule_sets {
+            for (lo, hi) in [
+                (0, 2000),
+                (95, 1234),
+                (1000, 999),
+                (5, 5),
+                (99_000, 101_000),
+            ] {
+                let expected: Vec<usize> = (lo..=hi).filter(|n| brute_force(*n, rules)).collect();
+                assert_eq!(rules.matching(lo, hi), expected);
+                assert_eq!(rules.count(lo, hi), expected.len() as u64);
+            }
+        }
+        assert!("pair,run=x".parse::<Rules>().is_err());
+        assert!("bogus".parse::<Rules>().is_err());
+        assert_eq!(part1("1-9"), 0);
+        assert_eq!(part1("100000-999999"), 2919);
+        // far too many numbers to iterate over
+        let rules = Rules::new().with(Rule::NonDecreasing);
+        assert_eq!(
+            rules.count(10_000_000_000_000_000_000, usize::MAX),
+            2_220_055
+        );
+        assert_eq!(rules.count(0, usize::MAX), 9_126_955);
+    }
 }
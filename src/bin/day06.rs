@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
@@ -9,9 +10,11 @@ struct Input {
 }
 
 impl FromStr for Input {
-    type Err = ();
+    type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (cent, sat) = s.split_once(')').unwrap();
+        let (cent, sat) = s
+            .split_once(')')
+            .ok_or_else(|| format!("Invalid orbit {s:?}"))?;
         Ok(Input {
             cent: cent.to_owned(),
             sat: sat.to_owned(),
@@ -19,53 +22,389 @@ impl FromStr for Input {
     }
 }
 
-fn setup(input: &[Input]) -> HashMap<&str, &str> {
-    input
-        .iter()
-        .map(|i| (i.sat.as_str(), i.cent.as_str()))
-        .collect()
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum OrbitError {
+    DuplicateParent { body: String, parents: [String; 2] },
+    NoRoot,
+    MultipleRoots(Vec<String>),
+    Cycle(Vec<String>),
+    UnknownBody(String),
+    NoParent(String),
 }
 
-fn part1(input: &[Input]) -> usize {
-    let map = setup(input);
-    let mut n = 0;
-    for k in map.keys() {
-        let mut obj = *k;
-        while obj != "COM" {
-            n += 1;
-            obj = map[obj];
+impl fmt::Display for OrbitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrbitError::DuplicateParent { body, parents } => {
+                write!(f, "{body} orbits both {} and {}", parents[0], parents[1])
+            }
+            OrbitError::NoRoot => write!(f, "every body orbits something"),
+            OrbitError::MultipleRoots(roots) => {
+                write!(f, "more than one root: {}", roots.join(", "))
+            }
+            OrbitError::Cycle(bodies) => write!(f, "orbit cycle: {}", bodies.join(")")),
+            OrbitError::UnknownBody(body) => write!(f, "no body named {body}"),
+            OrbitError::NoParent(body) => write!(f, "{body} doesn't orbit anything"),
         }
     }
-    n
 }
 
-fn part2(input: &[Input]) -> usize {
-    let map = setup(input);
-    let mut santa_path: HashMap<&str, usize> = HashMap::new();
-    let mut obj = map["SAN"];
-    let mut steps = 0;
-    while obj != "COM" {
-        santa_path.insert(obj, steps);
-        steps += 1;
-        obj = map[obj];
-    }
-    santa_path.insert("COM", steps);
-    obj = map["YOU"];
-    steps = 0;
-    while obj != "COM" {
-        if santa_path.contains_key(obj) {
-            return santa_path[obj] + steps;
-        }
-        steps += 1;
-        obj = map[obj];
-    }
-    steps + santa_path["COM"]
+impl From<OrbitError> for String {
+    fn from(e: OrbitError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Totals for the subtree rooted at a body (including the body itself).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct SubtreeStats {
+    bodies: usize,
+    /// Longest chain of orbits below the body
+    height: usize,
+    /// Direct and indirect orbits among the bodies of the subtree
+    orbits: usize,
+}
+
+struct OrbitTree {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    parent: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    root: usize,
+    depth: Vec<usize>,
+    // ancestors[k][b] is the 2^k'th ancestor of b (the root is its own)
+    ancestors: Vec<Vec<usize>>,
+    stats: Vec<SubtreeStats>,
+}
+
+impl OrbitTree {
+    /// Builds the tree, reporting every problem found with the orbit map.
+    fn new(input: &[Input]) -> Result<Self, Vec<OrbitError>> {
+        let mut names: Vec<String> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut id = |name: &str| -> usize {
+            *index.entry(name.to_owned()).or_insert_with(|| {
+                names.push(name.to_owned());
+                names.len() - 1
+            })
+        };
+        let edges: Vec<(usize, usize)> = input.iter().map(|i| (id(&i.cent), id(&i.sat))).collect();
+        let n = names.len();
+
+        let mut errors = Vec::new();
+        let mut parent: Vec<Option<usize>> = vec![None; n];
+        let mut children = vec![Vec::new(); n];
+        for (cent, sat) in edges {
+            match parent[sat] {
+                Some(p) if p != cent => errors.push(OrbitError::DuplicateParent {
+                    body: names[sat].clone(),
+                    parents: [names[p].clone(), names[cent].clone()],
+                }),
+                Some(_) => {}
+                None => {
+                    parent[sat] = Some(cent);
+                    children[cent].push(sat);
+                }
+            }
+        }
+
+        let roots: Vec<usize> = (0..n).filter(|b| parent[*b].is_none()).collect();
+        match roots.len() {
+            0 if n > 0 => errors.push(OrbitError::NoRoot),
+            0 | 1 => {}
+            _ => errors.push(OrbitError::MultipleRoots(
+                roots.iter().map(|r| names[*r].clone()).collect(),
+            )),
+        }
+
+        // breadth-first from the root gives every depth, and an order in
+        // which each body comes after its parent (if there are several
+        // roots, this is only used to find what's left over)
+        let mut depth = vec![usize::MAX; n];
+        let mut order = Vec::with_capacity(n);
+        for root in &roots {
+            depth[*root] = 0;
+            order.push(*root);
+        }
+        let mut i = 0;
+        while i < order.len() {
+            let b = order[i];
+            for c in &children[b] {
+                depth[*c] = depth[b] + 1;
+                order.push(*c);
+            }
+            i += 1;
+        }
+        // anything not under a root is in or hanging off a cycle, and
+        // always has a parent to follow
+        let mut seen = vec![false; n];
+        for start in (0..n).filter(|b| depth[*b] == usize::MAX) {
+            let mut path = Vec::new();
+            let mut b = start;
+            while !seen[b] {
+                seen[b] = true;
+                path.push(b);
+                b = parent[b].unwrap();
+            }
+            if let Some(pos) = path.iter().position(|p| *p == b) {
+                let mut cycle = vec![names[b].clone()];
+                cycle.extend(path[pos..].iter().rev().map(|p| names[*p].clone()));
+                errors.push(OrbitError::Cycle(cycle));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let levels = (usize::BITS - n.leading_zeros()).max(1) as usize;
+        let mut ancestors = vec![(0..n).map(|b| parent[b].unwrap_or(b)).collect::<Vec<_>>()];
+        for k in 1..levels {
+            let prev = &ancestors[k - 1];
+            let next = (0..n).map(|b| prev[prev[b]]).collect();
+            ancestors.push(next);
+        }
+
+        let mut stats = vec![SubtreeStats::default(); n];
+        for b in order.iter().rev() {
+            let mut s = SubtreeStats {
+                bodies: 1,
+                ..Default::default()
+            };
+            for c in &children[*b] {
+                let cs = &stats[*c];
+                s.bodies += cs.bodies;
+                s.height = s.height.max(cs.height + 1);
+                // each body below c orbits b as well
+                s.orbits += cs.orbits + cs.bodies;
+            }
+            stats[*b] = s;
+        }
+
+        Ok(Self {
+            names,
+            index,
+            parent,
+            children,
+            root: roots.first().copied().unwrap_or(0),
+            depth,
+            ancestors,
+            stats,
+        })
+    }
+
+    fn id(&self, name: &str) -> Result<usize, OrbitError> {
+        self.index
+            .get(name)
+            .copied()
+            .ok_or_else(|| OrbitError::UnknownBody(name.to_owned()))
+    }
+
+    fn parent(&self, name: &str) -> Result<Option<&str>, OrbitError> {
+        Ok(self.parent[self.id(name)?].map(|p| self.names[p].as_str()))
+    }
+
+    fn depth(&self, name: &str) -> Result<usize, OrbitError> {
+        Ok(self.depth[self.id(name)?])
+    }
+
+    fn stats(&self, name: &str) -> Result<SubtreeStats, OrbitError> {
+        Ok(self.stats[self.id(name)?])
+    }
+
+    fn total_orbits(&self) -> usize {
+        self.depth.iter().sum()
+    }
+
+    fn ancestor(&self, mut b: usize, steps: usize) -> usize {
+        for (k, level) in self.ancestors.iter().enumerate() {
+            if steps & (1 << k) != 0 {
+                b = level[b];
+            }
+        }
+        b
+    }
+
+    fn lca_id(&self, a: usize, b: usize) -> usize {
+        let (mut a, mut b) = if self.depth[a] >= self.depth[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        a = self.ancestor(a, self.depth[a] - self.depth[b]);
+        if a == b {
+            return a;
+        }
+        for level in self.ancestors.iter().rev() {
+            if level[a] != level[b] {
+                a = level[a];
+                b = level[b];
+            }
+        }
+        self.ancestors[0][a]
+    }
+
+    /// The nearest body that both `a` and `b` orbit (directly or not), or
+    /// one of them if it orbits the other.
+    fn lca(&self, a: &str, b: &str) -> Result<&str, OrbitError> {
+        Ok(&self.names[self.lca_id(self.id(a)?, self.id(b)?)])
+    }
+
+    /// Number of orbit edges between two bodies.
+    fn distance(&self, a: &str, b: &str) -> Result<usize, OrbitError> {
+        let (a, b) = (self.id(a)?, self.id(b)?);
+        Ok(self.depth[a] + self.depth[b] - 2 * self.depth[self.lca_id(a, b)])
+    }
+
+    /// Orbital transfers needed to move from the body `a` orbits to the
+    /// body `b` orbits.
+    fn transfers(&self, a: &str, b: &str) -> Result<usize, OrbitError> {
+        let pa = self
+            .parent(a)?
+            .ok_or_else(|| OrbitError::NoParent(a.to_owned()))?;
+        let pb = self
+            .parent(b)?
+            .ok_or_else(|| OrbitError::NoParent(b.to_owned()))?;
+        self.distance(pa, pb)
+    }
+
+    /// The bodies along the way from `a` to `b`, both included.
+    fn path(&self, a: &str, b: &str) -> Result<Vec<&str>, OrbitError> {
+        let (a, b) = (self.id(a)?, self.id(b)?);
+        let lca = self.lca_id(a, b);
+        let mut up = vec![a];
+        while *up.last().unwrap() != lca {
+            up.push(self.ancestors[0][*up.last().unwrap()]);
+        }
+        let mut down = vec![b];
+        while *down.last().unwrap() != lca {
+            down.push(self.ancestors[0][*down.last().unwrap()]);
+        }
+        down.pop();
+        up.extend(down.iter().rev());
+        Ok(up.iter().map(|b| self.names[*b].as_str()).collect())
+    }
+
+    /// Renders the orbits as a Graphviz digraph, from each body to its
+    /// satellites. If `highlight` names two bodies, the path between them
+    /// is drawn in red.
+    fn to_dot(&self, highlight: Option<(&str, &str)>) -> Result<String, OrbitError> {
+        let path = match highlight {
+            Some((a, b)) => self.path(a, b)?,
+            None => Vec::new(),
+        };
+        let path: HashSet<usize> = path.iter().map(|name| self.index[*name]).collect();
+        let on_path = |b: usize| path.contains(&b);
+        // names are quoted, so only quotes and backslashes need escaping
+        let quoted = |b: usize| {
+            format!(
+                "\"{}\"",
+                self.names[b].replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        };
+        let mut dot = String::from("digraph orbits {\n    node [shape=circle];\n");
+        for b in (0..self.names.len()).filter(|b| on_path(*b)) {
+            dot.push_str(&format!(
+                "    {} [style=filled, fillcolor=\"#ffb0b0\"];\n",
+                quoted(b)
+            ));
+        }
+        for (b, sats) in self.children.iter().enumerate() {
+            for s in sats {
+                let attrs = if on_path(b) && on_path(*s) {
+                    " [color=red, penwidth=3]"
+                } else {
+                    ""
+                };
+                dot.push_str(&format!("    {} -> {}{attrs};\n", quoted(b), quoted(*s)));
+            }
+        }
+        dot.push_str("}\n");
+        Ok(dot)
+    }
+
+    /// Draws the tree from the root down, one body per line.
+    fn to_tree_string(&self) -> String {
+        let mut out = String::new();
+        if self.names.is_empty() {
+            return out;
+        }
+        // (body, prefix for its line, prefix for its satellites' lines)
+        let mut stack = vec![(self.root, String::new(), String::new())];
+        while let Some((b, line, below)) = stack.pop() {
+            out.push_str(&format!("{line}{}\n", self.names[b]));
+            let sats = &self.children[b];
+            for (i, s) in sats.iter().enumerate().rev() {
+                let (branch, cont) = if i + 1 == sats.len() {
+                    ("\u{2514}\u{2500}\u{2500} ", "    ")
+                } else {
+                    ("\u{251c}\u{2500}\u{2500} ", "\u{2502}   ")
+                };
+                stack.push((*s, format!("{below}{branch}"), format!("{below}{cont}")));
+            }
+        }
+        out
+    }
+}
+
+fn part1(tree: &OrbitTree) -> usize {
+    tree.total_orbits()
+}
+
+fn part2(tree: &OrbitTree) -> usize {
+    tree.transfers("YOU", "SAN").unwrap()
+}
+
+// Each argument is either a body, to show its depth and subtree, or two
+// bodies as `A:B` to show how they're related. `--tree` prints the whole
+// tree, and `--dot` or `--dot=A:B` prints it as Graphviz input.
+fn query_tree(tree: &OrbitTree, query: &str) -> Result<(), String> {
+    if query == "--tree" {
+        print!("{}", tree.to_tree_string());
+        return Ok(());
+    }
+    if let Some(highlight) = query.strip_prefix("--dot") {
+        let highlight = match highlight.strip_prefix('=') {
+            None if highlight.is_empty() => None,
+            Some(h) if h.contains(':') => h.split_once(':'),
+            _ => return Err("expected --dot or --dot=A:B".to_string()),
+        };
+        print!("{}", tree.to_dot(highlight)?);
+        return Ok(());
+    }
+    match query.split_once(':') {
+        Some((a, b)) => println!(
+            "{query}: common ancestor {}, distance {}",
+            tree.lca(a, b)?,
+            tree.distance(a, b)?
+        ),
+        None => {
+            let stats = tree.stats(query)?;
+            println!(
+                "{query}: depth {}, {} bodies below, height {}, {} orbits within",
+                tree.depth(query)?,
+                stats.bodies - 1,
+                stats.height,
+                stats.orbits
+            );
+        }
+    }
+    Ok(())
 }
 
 fn main() {
     let input: Vec<Input> = read_input();
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    let tree = match OrbitTree::new(&input) {
+        Ok(tree) => tree,
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{e}"));
+            std::process::exit(1);
+        }
+    };
+    println!("Part 1: {}", part1(&tree));
+    println!("Part 2: {}", part2(&tree));
+    for query in std::env::args().skip(1) {
+        query_tree(&tree, &query).unwrap_or_else(|e| println!("{query}: {e}"));
+    }
 }
 
 #[cfg(test)]
@@ -76,8 +415,113 @@ mod tests {
     #[test]
     fn day06_test() {
         let input: Vec<Input> = test_input(include_str!("day06.testinput"));
-        assert_eq!(part1(&input), 42);
+        assert_eq!(part1(&OrbitTree::new(&input).unwrap()), 42);
+        let input: Vec<Input> = test_input(include_str!("day06.testinput2"));
+        assert_eq!(part2(&OrbitTree::new(&input).unwrap()), 4);
+    }
+
+    #[test]
+    fn orbit_tree_test() {
+        let input: Vec<Input> = test_input(include_str!("day06.testinput2"));
+        let tree = OrbitTree::new(&input).unwrap();
+        assert_eq!(tree.depth("L"), Ok(7));
+        assert_eq!(tree.lca("L", "I"), Ok("D"));
+        assert_eq!(tree.lca("K", "E"), Ok("E"));
+        assert_eq!(tree.distance("YOU", "SAN"), Ok(6));
+        assert_eq!(tree.distance("COM", "COM"), Ok(0));
+        assert_eq!(tree.transfers("L", "H"), Ok(6));
+        assert_eq!(
+            tree.transfers("COM", "H"),
+            Err(OrbitError::NoParent("COM".to_string()))
+        );
+        assert_eq!(tree.parent("COM"), Ok(None));
+        assert_eq!(
+            tree.depth("X"),
+            Err(OrbitError::UnknownBody("X".to_string()))
+        );
+        assert_eq!(
+            tree.stats("E"),
+            Ok(SubtreeStats {
+                bodies: 6,
+                height: 3,
+                orbits: 10,
+            })
+        );
+        assert_eq!(tree.stats("COM").unwrap().orbits, tree.total_orbits());
+
+        let input: Vec<Input> = test_input("COM)A\nA)B\nC)B\nD)E\nE)F\nF)D\nF)G");
+        assert_eq!(
+            OrbitTree::new(&input).err(),
+            Some(vec![
+                OrbitError::DuplicateParent {
+                    body: "B".to_string(),
+                    parents: ["A".to_string(), "C".to_string()],
+                },
+                OrbitError::MultipleRoots(vec!["COM".to_string(), "C".to_string()]),
+                OrbitError::Cycle(["D", "E", "F", "D"].map(String::from).to_vec()),
+            ])
+        );
+        let input: Vec<Input> = test_input("COM)A\nD)E\nE)F\nF)D\nF)G");
+        assert_eq!(
+            OrbitTree::new(&input).err(),
+            Some(vec![OrbitError::Cycle(
+                ["D", "E", "F", "D"].map(String::from).to_vec()
+            )])
+        );
+        let input: Vec<Input> = test_input("A)B\nB)A");
+        assert_eq!(
+            OrbitTree::new(&input).err(),
+            Some(vec![
+                OrbitError::NoRoot,
+                OrbitError::Cycle(["A", "B", "A"].map(String::from).to_vec()),
+            ])
+        );
+    }
+
+    #[test]
+    fn export_test() {
         let input: Vec<Input> = test_input(include_str!("day06.testinput2"));
-        assert_eq!(part2(&input), 4);
+        let tree = OrbitTree::new(&input).unwrap();
+        assert_eq!(
+            tree.path("YOU", "SAN"),
+            Ok(vec!["YOU", "K", "J", "E", "D", "I", "SAN"])
+        );
+        assert_eq!(tree.path("D", "L"), Ok(vec!["D", "E", "J", "K", "L"]));
+
+        let dot = tree.to_dot(Some(("YOU", "SAN"))).unwrap();
+        assert!(dot.starts_with("digraph orbits {\n"));
+        assert_eq!(dot.matches(" -> ").count(), 13);
+        assert_eq!(dot.matches("color=red").count(), 6);
+        assert!(dot.contains("    \"D\" -> \"I\" [color=red, penwidth=3];\n"));
+        assert!(dot.contains("    \"B\" -> \"G\";\n"));
+        assert_eq!(
+            tree.to_dot(Some(("YOU", "X"))),
+            Err(OrbitError::UnknownBody("X".to_string()))
+        );
+        assert!(!tree.to_dot(None).unwrap().contains("red"));
+        assert!(query_tree(&tree, "--dot=YOU").is_err());
+        assert!(query_tree(&tree, "--dotty").is_err());
+        assert_eq!(
+            query_tree(&tree, "--dot=YOU:X"),
+            Err("no body named X".to_string())
+        );
+
+        let input: Vec<Input> = test_input("COM)A\"B\nA\"B)C\\D");
+        let dot = OrbitTree::new(&input).unwrap().to_dot(None).unwrap();
+        assert!(dot.contains("    \"A\\\"B\" -> \"C\\\\D\";\n"));
+
+        let input: Vec<Input> = test_input("COM)B\nB)C\nC)D\nB)E\nCOM)F");
+        let tree = OrbitTree::new(&input).unwrap();
+        assert_eq!(
+            tree.to_tree_string(),
+            "\
+COM
+\u{251c}\u{2500}\u{2500} B
+\u{2502}   \u{251c}\u{2500}\u{2500} C
+\u{2502}   \u{2502}   \u{2514}\u{2500}\u{2500} D
+\u{2502}   \u{2514}\u{2500}\u{2500} E
+\u{2514}\u{2500}\u{2500} F
+"
+        );
     }
 }
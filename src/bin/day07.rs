@@ -1,8 +1,8 @@
-use itertools::Itertools;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
-use advent2019::intcode::{IntcodeVM, ProgMem, RunErr};
+use advent2019::amplifier::{Circuit, Topology};
+use advent2019::intcode::ProgMem;
 
 /*
 struct Amp<'a> {
@@ -59,48 +59,14 @@ fn run_with_phases(program: &ProgMem, phases: &[i64]) -> i64 {
 }
 */
 
-fn run_with_phases(program: &ProgMem, phases: &[i64]) -> i64 {
-    let mut vms = Vec::new();
-    for p in phases {
-        let mut vm = IntcodeVM::with_mem(program);
-        vm.input_queue.push_back(*p);
-        vms.push(vm);
-    }
-
-    let mut nextv = 0;
-    let mut repeat = true;
-    while repeat {
-        repeat = false;
-        for vm in vms.iter_mut() {
-            vm.input_queue.push_back(nextv);
-            match vm.run_with_cb(&mut || None, &mut |v| {
-                nextv = v;
-            }) {
-                Ok(_) => {}
-                Err(RunErr::InputNeeded) => {
-                    repeat = true;
-                }
-                _ => panic!(),
-            }
-        }
-    }
-    nextv
-}
-
 fn part1(input: &ProgMem) -> i64 {
-    (0..5)
-        .permutations(5)
-        .map(|v| run_with_phases(input, &v))
-        .max()
-        .unwrap()
+    let circuit = Circuit::new(input, Topology::chain(5));
+    circuit.optimize(&[0, 1, 2, 3, 4]).unwrap().unwrap().signal
 }
 
 fn part2(input: &ProgMem) -> i64 {
-    (5..10)
-        .permutations(5)
-        .map(|v| run_with_phases(input, &v))
-        .max()
-        .unwrap()
+    let circuit = Circuit::new(input, Topology::Ring(5));
+    circuit.optimize(&[5, 6, 7, 8, 9]).unwrap().unwrap().signal
 }
 
 fn main() {
@@ -1,45 +1,199 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
 use std::str::FromStr;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
+extern crate advent2019;
+use advent2019::ocr::{recognize, Raster};
+use advent2019::pnm::{create_in, upscale, write_pbm, write_pgm, write_ppm};
 
-struct Sif<const W: usize, const H: usize> {
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Pnm {
+    Pbm,
+    Pgm,
+    Ppm,
+}
+
+// Writes SIF pixels as an image. Black and white map directly; transparent
+// pixels are white in a bitmap, mid-grey in a greymap and magenta in a pixmap.
+// Any other digit isn't a colour, and shows as dark grey or red.
+fn write_sif_pixels<Wr: Write>(
+    out: &mut Wr,
+    kind: Pnm,
+    width: usize,
+    pixels: &[u8],
+    scale: usize,
+) -> io::Result<()> {
+    let pixels = upscale(width, pixels, scale);
+    let width = width * scale;
+    match kind {
+        Pnm::Pbm => write_pbm(
+            out,
+            &Raster::new(width, pixels.iter().map(|p| *p == 0).collect()),
+        ),
+        Pnm::Pgm => {
+            let grey: Vec<u8> = pixels
+                .iter()
+                .map(|p| match p {
+                    0 => 0,
+                    1 => 255,
+                    2 => 128,
+                    _ => 64,
+                })
+                .collect();
+            write_pgm(out, width, &grey)
+        }
+        Pnm::Ppm => {
+            let rgb: Vec<[u8; 3]> = pixels
+                .iter()
+                .map(|p| match p {
+                    0 => [0, 0, 0],
+                    1 => [255, 255, 255],
+                    2 => [255, 0, 255],
+                    _ => [255, 0, 0],
+                })
+                .collect();
+            write_ppm(out, width, &rgb)
+        }
+    }
+}
+
+/// A Space Image Format image: `width` x `height` layers of digits, where
+/// 0 is black, 1 is white and 2 is transparent.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Sif {
+    width: usize,
+    height: usize,
     pixels: Vec<u8>,
 }
 
-impl<const W: usize, const H: usize> FromStr for Sif<W, H> {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let pixels: Vec<u8> = s.chars().map(|c| (c as u8) - b'0').collect();
-        if pixels.len() % (W * H) != 0 {
+impl Sif {
+    /// Accepts any digits, since the checksum example uses more than the
+    /// three colours; see `validate`.
+    fn new(width: usize, height: usize, s: &str) -> Result<Self, String> {
+        if width == 0 || height == 0 {
+            return Err(format!("Invalid size {width}x{height}"));
+        }
+        let pixels = s
+            .trim()
+            .chars()
+            .enumerate()
+            .map(|(i, c)| match c.to_digit(10) {
+                Some(d) => Ok(d as u8),
+                None => Err(format!("Invalid pixel {c:?} at {i}")),
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        if pixels.len() % (width * height) != 0 {
             Err(format!(
-                "Pixel count {} not a multiple of {W}x{H}",
+                "Pixel count {} not a multiple of {width}x{height}",
                 pixels.len()
             ))
         } else {
-            Ok(Self { pixels })
+            Ok(Self {
+                width,
+                height,
+                pixels,
+            })
         }
     }
-}
-impl<const W: usize, const H: usize> Sif<W, H> {
-    fn part1(&self) -> usize {
-        self.pixels
-            .chunks(W * H)
-            .min_by_key(|layer| layer.iter().filter(|p| **p == 0).count())
+
+    /// Splits `image` across `nlayers` layers, with each pixel opaque in
+    /// exactly one of them, so that compositing gives `image` back.
+    fn encode(width: usize, image: &[u8], nlayers: usize) -> Result<Self, String> {
+        let size = image.len();
+        if width == 0 || size == 0 || !size.is_multiple_of(width) {
+            return Err(format!("Can't split {size} pixels into rows of {width}"));
+        }
+        let mut pixels = vec![2; size * nlayers.max(1)];
+        for (i, p) in image.iter().enumerate() {
+            pixels[(i % nlayers.max(1)) * size + i] = *p;
+        }
+        Ok(Self {
+            width,
+            height: size / width,
+            pixels,
+        })
+    }
+
+    /// Checks that every pixel is black, white or transparent.
+    fn validate(&self) -> Result<(), String> {
+        match self.pixels.iter().position(|p| *p > 2) {
+            Some(i) => Err(format!(
+                "Invalid colour {} in layer {} at ({}, {})",
+                self.pixels[i],
+                i / self.layer_size(),
+                i % self.width,
+                (i % self.layer_size()) / self.width
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn layer_size(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn layers(&self) -> impl DoubleEndedIterator<Item = &[u8]> {
+        self.pixels.chunks(self.layer_size())
+    }
+
+    /// Counts of each digit in every layer.
+    fn histograms(&self) -> Vec<[usize; 10]> {
+        self.layers()
             .map(|layer| {
-                layer.iter().fold((0, 0), |(ones, twos), p| match p {
-                    1 => (ones + 1, twos),
-                    2 => (ones, twos + 1),
-                    _ => (ones, twos),
+                layer.iter().fold([0; 10], |mut h, p| {
+                    h[*p as usize] += 1;
+                    h
                 })
             })
-            .map(|(ones, twos)| ones * twos)
-            .unwrap()
+            .collect()
+    }
+
+    /// Picks the first layer with the smallest `key` of its histogram and
+    /// multiplies its counts of `a` and `b`.
+    fn checksum<K, F>(&self, key: F, a: u8, b: u8) -> Option<usize>
+    where
+        K: Ord,
+        F: Fn(&[usize; 10]) -> K,
+    {
+        self.histograms()
+            .iter()
+            .min_by_key(|h| key(h))
+            .map(|h| h[a as usize] * h[b as usize])
+    }
+
+    fn write_layer<Wr: Write>(
+        &self,
+        out: &mut Wr,
+        kind: Pnm,
+        idx: usize,
+        scale: usize,
+    ) -> io::Result<()> {
+        let size = self.layer_size();
+        write_sif_pixels(
+            out,
+            kind,
+            self.width,
+            &self.pixels[idx * size..(idx + 1) * size],
+            scale,
+        )
+    }
+    fn write_composite<Wr: Write>(&self, out: &mut Wr, kind: Pnm, scale: usize) -> io::Result<()> {
+        write_sif_pixels(out, kind, self.width, &self.part2(), scale)
+    }
+    /// Writes every layer, front to back, as consecutive frames of one file.
+    fn write_animation<Wr: Write>(&self, out: &mut Wr, kind: Pnm, scale: usize) -> io::Result<()> {
+        self.layers()
+            .try_for_each(|layer| write_sif_pixels(out, kind, self.width, layer, scale))
+    }
+    fn part1(&self) -> Option<usize> {
+        self.checksum(|h| h[0], 1, 2)
     }
     fn part2(&self) -> Vec<u8> {
-        self.pixels
-            .chunks(W * H)
+        self.layers()
             .rev()
-            .fold(vec![2; W * H], |mut v, layer| {
+            .fold(vec![2; self.layer_size()], |mut v, layer| {
                 v.iter_mut().zip(layer).for_each(|(vp, lp)| {
                     if *lp != 2 {
                         *vp = *lp;
@@ -50,30 +204,71 @@ impl<const W: usize, const H: usize> Sif<W, H> {
     }
 }
 
-fn part1(input: &Sif<25, 6>) -> usize {
-    input.part1()
+impl FromStr for Sif {
+    type Err = String;
+    /// Parses an image with a size header, as in `25x6:0122...`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (size, pixels) = s.split_once(':').ok_or("Missing size header")?;
+        let (width, height) = parse_size(size)?;
+        Self::new(width, height, pixels)
+    }
+}
+
+impl fmt::Display for Sif {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}:", self.width, self.height)?;
+        self.pixels.iter().try_for_each(|p| write!(f, "{p}"))
+    }
 }
 
-fn part2(input: &Sif<25, 6>) {
-    input.part2().chunks(25).for_each(|v| {
-        println!(
-            "{}",
-            v.iter()
-                .map(|p| match p {
-                    0 => ' ',
-                    1 => '\u{2588}',
-                    _ => panic!(),
-                })
-                .collect::<String>()
-        );
-    });
+fn parse_size(s: &str) -> Result<(usize, usize), String> {
+    s.split_once('x')
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .ok_or_else(|| format!("Invalid size {s:?}"))
+}
+
+fn part1(input: &Sif) -> String {
+    input
+        .part1()
+        .map_or_else(|| "no layers".to_string(), |n| n.to_string())
 }
 
+fn part2(input: &Sif) -> String {
+    if let Err(e) = input.validate() {
+        return e;
+    }
+    let raster = Raster::from_layer(&input.part2(), input.width);
+    recognize(&raster).unwrap_or_else(|e| format!("\n{}{e}", raster.render('\u{2588}', ' ')))
+}
+
+fn export_images(input: &Sif, dir: &Path) -> io::Result<()> {
+    input.write_composite(&mut create_in(dir, "day08.pbm")?, Pnm::Pbm, 10)?;
+    input.write_animation(&mut create_in(dir, "day08_layers.ppm")?, Pnm::Ppm, 10)?;
+    input.write_layer(&mut create_in(dir, "day08_layer0.pgm")?, Pnm::Pgm, 0, 10)?;
+    // the composited image on its own, as a single-layer SIF
+    let flat = Sif::encode(input.width, &input.part2(), 1).map_err(io::Error::other)?;
+    writeln!(create_in(dir, "day08_flat.sif")?, "{flat}")
+}
+
+// usage: day08 [WxH [exportdir]]
+//
+// The size defaults to 25x6, and is ignored if the input has a size header.
+// Given a directory, the image and its layers are also saved there.
 fn main() {
-    let input: Vec<Sif<25, 6>> = read_input();
-    println!("Part 1: {}", part1(&input[0]));
-    println!("Part 2:");
-    part2(&input[0]);
+    let args: Vec<String> = std::env::args().collect();
+    let (width, height) = args.get(1).map_or(Ok((25, 6)), |s| parse_size(s)).unwrap();
+    let input: Vec<String> = read_input();
+    let sif = if input[0].contains(':') {
+        input[0].parse()
+    } else {
+        Sif::new(width, height, &input[0])
+    }
+    .unwrap();
+    println!("Part 1: {}", part1(&sif));
+    println!("Part 2: {}", part2(&sif));
+    if let Some(dir) = args.get(2) {
+        export_images(&sif, Path::new(dir)).unwrap();
+    }
 }
 
 #[cfg(test)]
@@ -83,10 +278,58 @@ mod tests {
 
     #[test]
     fn day08_test() {
-        let input: Vec<Sif<3, 2>> = test_input("123456789012");
-        assert_eq!(input[0].part1(), 1);
+        let input = Sif::new(3, 2, "123456789012").unwrap();
+        assert_eq!(input.part1(), Some(1));
+        assert_eq!(part1(&Sif::new(3, 2, "").unwrap()), "no layers");
 
-        let input: Vec<Sif<2, 2>> = test_input("0222112222120000");
+        let input: Vec<Sif> = test_input("2x2:0222112222120000");
         assert_eq!(input[0].part2(), [0, 1, 1, 0]);
+
+        let mut out = Vec::new();
+        input[0].write_composite(&mut out, Pnm::Pbm, 1).unwrap();
+        assert_eq!(out, b"P4\n2 2\n\x80\x40");
+        let mut out = Vec::new();
+        input[0].write_layer(&mut out, Pnm::Pgm, 1, 1).unwrap();
+        assert_eq!(out, b"P5\n2 2\n255\n\xff\xff\x80\x80");
+        let mut out = Vec::new();
+        input[0].write_animation(&mut out, Pnm::Ppm, 2).unwrap();
+        assert_eq!(out.windows(2).filter(|w| w == b"P6").count(), 4);
+        assert!(out.starts_with(b"P6\n4 4\n255\n\x00\x00\x00"));
+
+        // digits that aren't colours still export
+        let input = Sif::new(3, 2, "123456789012").unwrap();
+        let mut out = Vec::new();
+        input.write_animation(&mut out, Pnm::Ppm, 1).unwrap();
+        assert!(out.starts_with(b"P6\n3 2\n255\n\xff\xff\xff\xff\x00\xff\xff\x00\x00"));
+        let mut out = Vec::new();
+        input.write_layer(&mut out, Pnm::Pgm, 1, 1).unwrap();
+        assert_eq!(out, b"P5\n3 2\n255\n@@@\x00\xff\x80");
+    }
+
+    #[test]
+    fn sif_test() {
+        let input = Sif::new(3, 2, "123456789012").unwrap();
+        assert_eq!(
+            input.validate(),
+            Err("Invalid colour 3 in layer 0 at (2, 0)".to_string())
+        );
+        assert_eq!(input.histograms()[1][0], 1);
+        // the layer with the most 9s
+        assert_eq!(input.checksum(|h| std::cmp::Reverse(h[9]), 1, 2), Some(1));
+
+        assert!(Sif::new(2, 2, "01201").is_err());
+        assert!(Sif::new(2, 2, "01a0").is_err());
+        assert!("2x:0120".parse::<Sif>().is_err());
+        assert!("0120".parse::<Sif>().is_err());
+
+        let image = [0, 1, 1, 0, 1, 0];
+        assert!(Sif::encode(0, &image, 4).is_err());
+        assert!(Sif::encode(3, &[], 1).is_err());
+        assert!(Sif::encode(4, &image, 4).is_err());
+        let encoded = Sif::encode(3, &image, 4).unwrap();
+        assert_eq!(encoded.layers().count(), 4);
+        assert_eq!(encoded.validate(), Ok(()));
+        assert_eq!(encoded.part2(), image);
+        assert_eq!(encoded.to_string().parse::<Sif>(), Ok(encoded));
     }
 }
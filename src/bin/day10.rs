@@ -1,11 +1,14 @@
 use num::integer::gcd;
-use std::cmp::Ordering;
-use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::f64::consts::PI;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::vec::Vec;
 use ya_advent_lib::coords::Coord2D;
 use ya_advent_lib::infinite_grid::InfiniteGrid;
 use ya_advent_lib::read::read_input;
+extern crate advent2019;
+use advent2019::pnm::{upscale, write_pgm};
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 struct Slope {
@@ -17,22 +20,9 @@ impl From<Coord2D> for Slope {
         slope_with_d(c).0
     }
 }
-impl Slope {
-    fn angle(&self) -> f64 {
-        // atan2 is normally y.atan2(x). However, we want 0 to point up, where up is -y,
-        // increasing angles are clockwise, and output range is [0, 2pi)
-        let a = (self.x as f64).atan2(-self.y as f64);
-        if a < 0. {
-            a + 2. * PI
-        } else {
-            a
-        }
-    }
-}
 impl Ord for Slope {
     fn cmp(&self, other: &Self) -> Ordering {
-        // we don't expect to have any NaNs
-        self.angle().partial_cmp(&other.angle()).unwrap()
+        Sweep::default().cmp(*self, *other)
     }
 }
 impl PartialOrd for Slope {
@@ -41,87 +31,181 @@ impl PartialOrd for Slope {
     }
 }
 
+// Splits an offset into its direction in lowest terms and the number of
+// steps of that size it takes.
 fn slope_with_d(c: Coord2D) -> (Slope, i64) {
-    match (c.x, c.y) {
-        (0, 0) => (Slope { x: 0, y: 0 }, 0),
-        (n, 0) => (
-            Slope {
-                x: n.signum(),
-                y: 0,
-            },
-            n,
-        ),
-        (0, n) => (
-            Slope {
-                x: 0,
-                y: n.signum(),
-            },
-            n,
-        ),
-        (x, y) => {
-            let d = gcd(x, y);
-            (Slope { x: x / d, y: y / d }, d)
+    if c.x == 0 && c.y == 0 {
+        return (Slope { x: 0, y: 0 }, 0);
+    }
+    let d = gcd(c.x, c.y);
+    (
+        Slope {
+            x: c.x / d,
+            y: c.y / d,
+        },
+        d,
+    )
+}
+
+/// Which way the laser turns, and where it points first. Up is -y, so on
+/// screen, clockwise takes up to +x.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Sweep {
+    start: Slope,
+    clockwise: bool,
+}
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            start: Slope { x: 0, y: -1 },
+            clockwise: true,
+        }
+    }
+}
+impl Sweep {
+    // Positive if `b` is less than half a turn after `a` in the direction
+    // of the sweep
+    fn turn(&self, a: Slope, b: Slope) -> i64 {
+        let cross = a.x * b.y - a.y * b.x;
+        if self.clockwise {
+            cross.signum()
+        } else {
+            -cross.signum()
         }
     }
+
+    // 0 for directions in the half turn starting at `start`, 1 for the rest
+    fn half(&self, s: Slope) -> u8 {
+        match self.turn(self.start, s) {
+            1 => 0,
+            0 if s.x * self.start.x + s.y * self.start.y > 0 => 0,
+            _ => 1,
+        }
+    }
+
+    /// Orders directions by how far the laser turns to reach them, using
+    /// only integer arithmetic.
+    fn cmp(&self, a: Slope, b: Slope) -> Ordering {
+        self.half(a)
+            .cmp(&self.half(b))
+            .then_with(|| 0.cmp(&self.turn(a, b)))
+    }
 }
 
-fn mkset(input: &[String]) -> HashSet<Coord2D> {
-    // just using InfiniteGrid for its parsing.
-    let grid = InfiniteGrid::from_input(
-        input,
-        false,
-        |c, _, _| if c == '#' { Some(true) } else { None },
-    );
-    grid.iter()
-        .map(|((x, y), _)| Coord2D::new(*x, *y))
-        .collect()
+struct Field {
+    width: i64,
+    height: i64,
+    roids: HashSet<Coord2D>,
 }
 
-fn find_best(roids: &HashSet<Coord2D>) -> (Coord2D, usize) {
-    roids
-        .iter()
-        .map(|a| {
-            let slopes: HashSet<Slope> = roids
+impl Field {
+    fn new(input: &[String]) -> Self {
+        // just using InfiniteGrid for its parsing.
+        let grid = InfiniteGrid::from_input(
+            input,
+            false,
+            |c, _, _| if c == '#' { Some(true) } else { None },
+        );
+        Self {
+            width: input.iter().map(|l| l.len()).max().unwrap_or(0) as i64,
+            height: input.len() as i64,
+            roids: grid
                 .iter()
-                .filter(|b| *a != **b)
-                .map(|b| (*b - *a).into())
-                .collect();
-            (*a, slopes.len())
-        })
-        .max_by_key(|(_, c)| *c)
-        .unwrap()
-}
+                .map(|((x, y), _)| Coord2D::new(*x, *y))
+                .collect(),
+        }
+    }
 
-fn vaporize(roids: &HashSet<Coord2D>, center: Coord2D) -> Vec<Coord2D> {
-    let mut grouped: BTreeMap<Slope, BTreeSet<(i64, Coord2D)>> = BTreeMap::new();
-    roids.iter().filter(|r| **r != center).for_each(|r| {
-        let (s, d) = slope_with_d(*r - center);
-        grouped
-            .entry(s)
-            .and_modify(|e| {
-                e.insert((d, *r));
+    /// How many other asteroids can be seen from each asteroid.
+    fn visibility(&self) -> HashMap<Coord2D, usize> {
+        self.roids
+            .iter()
+            .map(|a| {
+                let slopes: HashSet<Slope> = self
+                    .roids
+                    .iter()
+                    .filter(|b| *a != **b)
+                    .map(|b| (*b - *a).into())
+                    .collect();
+                (*a, slopes.len())
             })
-            .or_insert(BTreeSet::from_iter([(d, *r)]));
-    });
-    let mut out = Vec::new();
-    let mut removed = true;
-    while removed {
-        removed = false;
-        grouped.values_mut().for_each(|set| {
-            if let Some((_, c)) = set.pop_first() {
-                removed = true;
-                out.push(c);
+            .collect()
+    }
+
+    fn find_best(&self) -> Option<(Coord2D, usize)> {
+        self.visibility()
+            .into_iter()
+            .max_by_key(|(c, n)| (*n, Reverse((c.y, c.x))))
+    }
+
+    /// Draws the visibility counts scaled to 0-9, with `.` for empty space.
+    fn heat_map(&self) -> String {
+        let counts = self.visibility();
+        let max = counts.values().copied().max().unwrap_or(0).max(1);
+        let mut out = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                out.push(match counts.get(&Coord2D::new(x, y)) {
+                    Some(n) => char::from_digit((n * 9 / max) as u32, 10).unwrap(),
+                    None => '.',
+                });
             }
-        });
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The heat map as a greymap, brighter for more visible asteroids.
+    fn write_heat_map<W: Write>(&self, out: &mut W, scale: usize) -> io::Result<()> {
+        let counts = self.visibility();
+        let max = counts.values().copied().max().unwrap_or(0).max(1);
+        let pixels: Vec<u8> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| Coord2D::new(x, y)))
+            .map(|c| match counts.get(&c) {
+                Some(n) => (64 + n * 191 / max) as u8,
+                None => 0,
+            })
+            .collect();
+        let width = self.width as usize;
+        write_pgm(out, width * scale, &upscale(width, &pixels, scale))
+    }
+
+    /// Every other asteroid in the order the laser at `center` destroys
+    /// them: one per direction on each turn, nearest first.
+    fn vaporize(&self, center: Coord2D, sweep: Sweep) -> Vec<Coord2D> {
+        let mut grouped: HashMap<Slope, Vec<(i64, Coord2D)>> = HashMap::new();
+        for r in self.roids.iter().filter(|r| **r != center) {
+            let (s, d) = slope_with_d(*r - center);
+            grouped.entry(s).or_default().push((d, *r));
+        }
+        let mut groups: Vec<(Slope, Vec<(i64, Coord2D)>)> = grouped.into_iter().collect();
+        groups.sort_by(|a, b| sweep.cmp(a.0, b.0));
+        for (_, g) in groups.iter_mut() {
+            // farthest first, so the nearest can be popped off the end
+            g.sort_by_key(|(d, _)| Reverse(*d));
+        }
+        let mut out = Vec::with_capacity(self.roids.len());
+        while !groups.is_empty() {
+            for (_, g) in groups.iter_mut() {
+                out.extend(g.pop().map(|(_, c)| c));
+            }
+            groups.retain(|(_, g)| !g.is_empty());
+        }
+        out
+    }
+
+    /// The `n`th asteroid destroyed, counting from 1.
+    fn nth_vaporized(&self, center: Coord2D, sweep: Sweep, n: usize) -> Option<Coord2D> {
+        n.checked_sub(1)
+            .and_then(|i| self.vaporize(center, sweep).get(i).copied())
     }
-    out
 }
 
 fn bothparts(input: &[String]) -> (usize, i64) {
-    let roids = mkset(input);
-    let (best, dist) = find_best(&roids);
-    let v = vaporize(&roids, best);
-    (dist, v[199].x * 100 + v[199].y)
+    let field = Field::new(input);
+    let (best, count) = field.find_best().unwrap();
+    let c = field.nth_vaporized(best, Sweep::default(), 200).unwrap();
+    (count, c.x * 100 + c.y)
 }
 
 fn main() {
@@ -129,6 +213,16 @@ fn main() {
     let (part1, part2) = bothparts(&input);
     println!("Part 1: {part1}");
     println!("Part 2: {part2}");
+    // pass a filename to also save a heat map of the visibility counts,
+    // or - to print it
+    match std::env::args().nth(1).as_deref() {
+        Some("-") => print!("{}", Field::new(&input).heat_map()),
+        Some(path) => {
+            let mut out = BufWriter::new(File::create(path).unwrap());
+            Field::new(&input).write_heat_map(&mut out, 8).unwrap();
+        }
+        None => {}
+    }
 }
 
 #[cfg(test)]
@@ -152,13 +246,82 @@ mod tests {
         assert_eq!(s, Slope { x: -1, y: 0 });
         let s: Slope = Coord2D::new(12, -10).into();
         assert_eq!(s, Slope { x: 6, y: -5 });
-        assert_eq!(Slope { x: 0, y: -1 }.angle(), 0.);
-        assert_eq!(Slope { x: 1, y: -1 }.angle(), PI / 4.);
-        assert_eq!(Slope { x: 1, y: 0 }.angle(), PI / 2.);
-        assert_eq!(Slope { x: 1, y: 1 }.angle(), PI * 3. / 4.);
-        assert_eq!(Slope { x: 0, y: 1 }.angle(), PI);
-        assert_eq!(Slope { x: -1, y: 1 }.angle(), PI * 5. / 4.);
-        assert_eq!(Slope { x: -1, y: 0 }.angle(), PI * 3. / 2.);
-        assert_eq!(Slope { x: -1, y: -1 }.angle(), PI * 7. / 4.);
+        let s: Slope = Coord2D::new(0, -7).into();
+        assert_eq!(s, Slope { x: 0, y: -1 });
+        assert_eq!(
+            slope_with_d(Coord2D::new(-6, 0)),
+            (Slope { x: -1, y: 0 }, 6)
+        );
+
+        // clockwise from up, in eighths of a turn
+        let compass = [
+            (0, -1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+        ]
+        .map(|(x, y)| Slope { x, y });
+        let mut sorted = compass;
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, compass);
+
+        // counter-clockwise starting from the right
+        let sweep = Sweep {
+            start: Slope { x: 1, y: 0 },
+            clockwise: false,
+        };
+        sorted.sort_by(|a, b| sweep.cmp(*a, *b));
+        assert_eq!(sorted, [2, 1, 0, 7, 6, 5, 4, 3].map(|i| compass[i]));
+
+        // nearly parallel directions that f64 angles can't tell apart
+        let a = Slope {
+            x: 1_000_000_000,
+            y: -999_999_999,
+        };
+        let b = Slope {
+            x: 999_999_998,
+            y: -999_999_997,
+        };
+        assert_eq!(a.cmp(&b), Ordering::Less);
+        assert_eq!(b.cmp(&a), Ordering::Greater);
+    }
+
+    #[test]
+    fn sweep_test() {
+        let input: Vec<String> = test_input(include_str!("day10.testinput"));
+        let field = Field::new(&input);
+        let best = Coord2D::new(11, 13);
+        assert_eq!(field.find_best(), Some((best, 210)));
+        let order = field.vaporize(best, Sweep::default());
+        assert_eq!(order.len(), 299);
+        assert_eq!(order[0], Coord2D::new(11, 12));
+        assert_eq!(order[298], Coord2D::new(11, 1));
+        assert_eq!(
+            field.nth_vaporized(best, Sweep::default(), 1),
+            Some(order[0])
+        );
+        assert_eq!(field.nth_vaporized(best, Sweep::default(), 0), None);
+        assert_eq!(field.nth_vaporized(best, Sweep::default(), 300), None);
+
+        let ccw = Sweep {
+            clockwise: false,
+            ..Sweep::default()
+        };
+        let order = field.vaporize(best, ccw);
+        assert_eq!(order[0], Coord2D::new(11, 12));
+        assert_eq!(order[1], Coord2D::new(10, 1));
+
+        let input: Vec<String> = test_input(".#..#\n.....\n#####\n....#\n...##");
+        let field = Field::new(&input);
+        assert_eq!(field.find_best(), Some((Coord2D::new(3, 4), 8)));
+        assert_eq!(field.heat_map(), ".7..7\n.....\n67775\n....7\n...97\n");
+        let mut out = Vec::new();
+        field.write_heat_map(&mut out, 1).unwrap();
+        assert!(out.starts_with(b"P5\n5 5\n255\n\x00"));
     }
 }
@@ -1,51 +1,336 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
 use std::vec::Vec;
 use ya_advent_lib::coords::{CDir, Coord2D, Turn};
 use ya_advent_lib::infinite_grid::InfiniteGrid;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
+use advent2019::heading::turned;
 use advent2019::intcode::{IntcodeVM, ProgMem, RunErr};
+use advent2019::ocr::{recognize, Raster};
+use advent2019::pnm::{create_in, sampled_frames, upscale, write_pbm, write_ppm, write_ppm_frames};
 
-fn run_robot(prog: &ProgMem, initial: bool) -> InfiniteGrid<bool> {
-    let mut pos = Coord2D::new(0, 0);
-    let mut dir = CDir::N;
-    let mut grid = InfiniteGrid::new(false);
-    let mut vm = IntcodeVM::with_mem(prog);
-    grid.set_c(pos, initial);
-    loop {
-        vm.input_queue
-            .push_back(if grid.get_c(pos) { 1 } else { 0 });
-        let mut outp = Vec::new();
-        match vm.run_with_cb(&mut || None, &mut |v| {
-            outp.push(v);
-        }) {
-            Ok(_) => {
-                break;
+/// One move of the robot: it reads the panel under it, paints it, turns and
+/// then steps forward.
+#[derive(Copy, Clone, Debug)]
+struct Step {
+    pos: Coord2D,
+    heading: CDir,
+    read: bool,
+    painted: bool,
+    turn: Turn,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let colour = |white| if white { "white" } else { "black" };
+        write!(
+            f,
+            "({}, {}) facing {:?}: read {}, painted {}, turned {}",
+            self.pos.x,
+            self.pos.y,
+            self.heading,
+            colour(self.read),
+            colour(self.painted),
+            match self.turn {
+                Turn::L => "left",
+                Turn::R => "right",
+            }
+        )
+    }
+}
+
+// Panels in images are white or black as painted, on a grey background for
+// those never painted.
+const UNPAINTED: [u8; 3] = [96, 96, 96];
+
+fn panel_colour(white: bool) -> [u8; 3] {
+    if white {
+        [255; 3]
+    } else {
+        [0; 3]
+    }
+}
+
+/// A recorded run of the painting robot, from which the hull can be
+/// reconstructed as it was after any number of steps.
+struct PaintSession {
+    initial: bool,
+    steps: Vec<Step>,
+}
+
+impl PaintSession {
+    /// Runs the robot, asking `brain` what to do given the colour under the
+    /// robot (true for white). It answers with the colour to paint and
+    /// whether to turn right, or None to stop.
+    fn record<F>(initial: bool, mut brain: F) -> Result<Self, RunErr>
+    where
+        F: FnMut(bool) -> Result<Option<(bool, Turn)>, RunErr>,
+    {
+        let mut grid = InfiniteGrid::new(false);
+        grid.set_c(Coord2D::new(0, 0), initial);
+        let mut pos = Coord2D::new(0, 0);
+        let mut heading = CDir::N;
+        let mut steps = Vec::new();
+        while let Some((painted, turn)) = brain(grid.get_c(pos))? {
+            steps.push(Step {
+                pos,
+                heading,
+                read: grid.get_c(pos),
+                painted,
+                turn,
+            });
+            grid.set_c(pos, painted);
+            heading = turned(heading, turn);
+            pos += heading;
+        }
+        Ok(Self { initial, steps })
+    }
+
+    fn run(prog: &ProgMem, initial: bool) -> Result<Self, RunErr> {
+        let mut vm = IntcodeVM::with_mem(prog);
+        let mut halted = false;
+        Self::record(initial, |white| {
+            if halted {
+                return Ok(None);
             }
-            Err(RunErr::InputNeeded) => {}
-            Err(_) => {
-                panic!();
+            vm.input_queue.push_back(i64::from(white));
+            let mut outp = Vec::new();
+            match vm.run_with_cb(&mut || None, &mut |v| outp.push(v)) {
+                Ok(_) => halted = true,
+                Err(RunErr::InputNeeded) => {}
+                Err(e) => return Err(e),
+            }
+            match outp[..] {
+                [] if halted => Ok(None),
+                [colour, turn] => Ok(Some((
+                    colour == 1,
+                    if turn == 0 { Turn::L } else { Turn::R },
+                ))),
+                _ => Err(RunErr::InvalidInput(format!("robot output {outp:?}"))),
+            }
+        })
+    }
+
+    /// Where the robot is, and which way it faces, after `n` steps.
+    fn robot_at(&self, n: usize) -> (Coord2D, CDir) {
+        match (
+            self.steps.get(n),
+            n.checked_sub(1).and_then(|i| self.steps.get(i)),
+        ) {
+            (Some(step), _) => (step.pos, step.heading),
+            (None, Some(last)) => {
+                let heading = turned(last.heading, last.turn);
+                (last.pos + heading, heading)
+            }
+            (None, None) => (Coord2D::new(0, 0), CDir::N),
+        }
+    }
+
+    /// The hull after the first `n` steps. Only panels that have been
+    /// painted (or the starting panel) are set.
+    fn grid_at(&self, n: usize) -> InfiniteGrid<bool> {
+        let mut grid = InfiniteGrid::new(false);
+        grid.set_c(Coord2D::new(0, 0), self.initial);
+        for step in self.steps.iter().take(n) {
+            grid.set_c(step.pos, step.painted);
+        }
+        grid
+    }
+
+    fn final_grid(&self) -> InfiniteGrid<bool> {
+        self.grid_at(self.steps.len())
+    }
+
+    fn paint_counts(&self) -> HashMap<(i64, i64), usize> {
+        let mut counts = HashMap::new();
+        for step in &self.steps {
+            *counts.entry(step.pos.into()).or_default() += 1;
+        }
+        counts
+    }
+
+    fn painted_at_least_once(&self) -> usize {
+        self.paint_counts().len()
+    }
+
+    fn painted_more_than_once(&self) -> usize {
+        self.paint_counts().values().filter(|n| **n > 1).count()
+    }
+
+    // (xmin, ymin, width, height) covering everywhere the robot goes
+    fn bounds(&self) -> (i64, i64, usize, usize) {
+        let points: Vec<Coord2D> = (0..=self.steps.len()).map(|n| self.robot_at(n).0).collect();
+        let xmin = points.iter().map(|c| c.x).min().unwrap();
+        let xmax = points.iter().map(|c| c.x).max().unwrap();
+        let ymin = points.iter().map(|c| c.y).min().unwrap();
+        let ymax = points.iter().map(|c| c.y).max().unwrap();
+        (
+            xmin,
+            ymin,
+            (xmax - xmin + 1) as usize,
+            (ymax - ymin + 1) as usize,
+        )
+    }
+
+    /// Draws the hull after `n` steps as `#` (white) and `.` (black), with
+    /// the robot shown by its heading.
+    fn render_frame(&self, n: usize) -> String {
+        let grid = self.grid_at(n);
+        let (robot, heading) = self.robot_at(n);
+        let (xmin, ymin, width, height) = self.bounds();
+        let mut out = String::new();
+        for y in ymin..ymin + height as i64 {
+            for x in xmin..xmin + width as i64 {
+                let c = Coord2D::new(x, y);
+                out.push(if c == robot {
+                    match heading {
+                        CDir::N => '^',
+                        CDir::E => '>',
+                        CDir::S => 'v',
+                        CDir::W => '<',
+                    }
+                } else if grid.get_c(c) {
+                    '#'
+                } else {
+                    '.'
+                });
             }
+            out.push('\n');
         }
-        assert_eq!(outp.len(), 2);
-        grid.set_c(pos, outp[0] == 1);
-        dir += if outp[1] == 0 { Turn::L } else { Turn::R };
-        pos += dir;
+        out
+    }
+
+    // The hull after `n` steps within `bounds`, with the robot in red.
+    fn frame_pixels(&self, bounds: (i64, i64, usize, usize), n: usize) -> Vec<[u8; 3]> {
+        let (xmin, ymin, width, height) = bounds;
+        let mut pixels = vec![UNPAINTED; width * height];
+        let idx = |c: Coord2D| (c.y - ymin) as usize * width + (c.x - xmin) as usize;
+        pixels[idx(Coord2D::new(0, 0))] = panel_colour(self.initial);
+        for step in self.steps.iter().take(n) {
+            pixels[idx(step.pos)] = panel_colour(step.painted);
+        }
+        pixels[idx(self.robot_at(n).0)] = [255, 0, 0];
+        pixels
+    }
+
+    /// Writes an animation of the robot at work, keeping one step in `every`.
+    fn write_frames<W: Write>(&self, out: &mut W, every: usize, scale: usize) -> io::Result<()> {
+        let bounds = self.bounds();
+        let frames =
+            sampled_frames(self.steps.len() + 1, every).map(|n| self.frame_pixels(bounds, n));
+        write_ppm_frames(out, bounds.2, frames, scale)
     }
-    grid
 }
 
-fn part1(input: &ProgMem) -> usize {
-    run_robot(input, false).iter().count()
+// Writes every panel the robot visited: white and black panels as painted,
+// with the unvisited panels in between shown in grey.
+fn write_hull<W: Write>(out: &mut W, grid: &InfiniteGrid<bool>, scale: usize) -> io::Result<()> {
+    let coords: Vec<(i64, i64)> = grid.iter().map(|((x, y), _)| (*x, *y)).collect();
+    let xmin = coords.iter().map(|c| c.0).min().unwrap_or(0);
+    let xmax = coords.iter().map(|c| c.0).max().unwrap_or(-1);
+    let ymin = coords.iter().map(|c| c.1).min().unwrap_or(0);
+    let ymax = coords.iter().map(|c| c.1).max().unwrap_or(-1);
+    let width = (xmax - xmin + 1) as usize;
+    let mut pixels = vec![UNPAINTED; width * (ymax - ymin + 1) as usize];
+    for ((x, y), white) in grid.iter() {
+        let idx = (y - ymin) as usize * width + (x - xmin) as usize;
+        pixels[idx] = panel_colour(*white);
+    }
+    write_ppm(out, width * scale, &upscale(width, &pixels, scale))
 }
 
-fn part2(input: &ProgMem) {
-    let grid = run_robot(input, true);
-    grid.print(|v| if v { '\u{2588}' } else { ' ' });
+fn export_images(
+    session: &PaintSession,
+    registration: &PaintSession,
+    dir: &Path,
+) -> io::Result<()> {
+    write_hull(
+        &mut create_in(dir, "day11_part1.ppm")?,
+        &session.final_grid(),
+        4,
+    )?;
+    session.write_frames(&mut create_in(dir, "day11_part1_frames.ppm")?, 25, 4)?;
+    let mut log = create_in(dir, "day11_part1_steps.txt")?;
+    for (n, step) in session.steps.iter().enumerate() {
+        writeln!(log, "{n:5} {step}")?;
+    }
+    write!(log, "\n{}", session.render_frame(session.steps.len()))?;
+    let raster = registration_raster(registration);
+    let scaled = Raster::new(
+        raster.width * 10,
+        upscale(raster.width, raster.pixels(), 10),
+    );
+    write_pbm(&mut create_in(dir, "day11_part2.pbm")?, &scaled)
+}
+
+// The white panels painted when starting on a white panel.
+fn registration_raster(session: &PaintSession) -> Raster {
+    let grid = session.final_grid();
+    Raster::from_points(grid.iter().filter(|(_, v)| **v).map(|((x, y), _)| (*x, *y)))
+}
+
+fn part1(session: &PaintSession) -> usize {
+    session.painted_at_least_once()
+}
+
+fn part2(session: &PaintSession) -> String {
+    let raster = registration_raster(session);
+    recognize(&raster).unwrap_or_else(|e| format!("\n{}{e}", raster.render('\u{2588}', ' ')))
 }
 
 fn main() {
     let input: Vec<ProgMem> = read_input();
-    println!("Part 1: {}", part1(&input[0]));
-    println!("Part 2:");
-    part2(&input[0]);
+    let session = PaintSession::run(&input[0], false).unwrap();
+    let registration = PaintSession::run(&input[0], true).unwrap();
+    println!("Part 1: {}", part1(&session));
+    println!(
+        "  {} steps, {} panels painted more than once",
+        session.steps.len(),
+        session.painted_more_than_once()
+    );
+    println!("Part 2: {}", part2(&registration));
+    // pass a directory to also save images of the painted hull there
+    if let Some(dir) = std::env::args().nth(1) {
+        export_images(&session, &registration, Path::new(&dir)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day11_test() {
+        // the robot's instructions from the puzzle's example
+        let mut script = [(1, 0), (0, 0), (1, 0), (1, 0), (0, 1), (1, 0), (1, 0)].into_iter();
+        let session = PaintSession::record(false, |_| {
+            Ok(script
+                .next()
+                .map(|(c, t)| (c == 1, if t == 0 { Turn::L } else { Turn::R })))
+        })
+        .unwrap();
+        assert_eq!(session.steps.len(), 7);
+        assert_eq!(session.painted_at_least_once(), 6);
+        assert_eq!(session.painted_more_than_once(), 1);
+        let step = session.steps[4];
+        assert_eq!((step.pos, step.heading), (Coord2D::new(0, 0), CDir::N));
+        assert!(step.read && !step.painted);
+
+        assert_eq!(
+            step.to_string(),
+            "(0, 0) facing N: read white, painted black, turned right"
+        );
+
+        assert_eq!(session.render_frame(0), "...\n.^.\n...\n");
+        assert_eq!(session.render_frame(4), "...\n.^.\n##.\n");
+        assert_eq!(session.render_frame(7), ".<#\n..#\n##.\n");
+
+        let mut out = Vec::new();
+        session.write_frames(&mut out, 3, 1).unwrap();
+        // frames 0, 3, 6 and 7
+        assert_eq!(out.windows(2).filter(|w| w == b"P6").count(), 4);
+    }
 }
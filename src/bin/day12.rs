@@ -1,16 +1,16 @@
-use itertools::Itertools;
 use lazy_static::lazy_static;
-use num::integer::lcm;
 use regex::Regex;
+use std::io;
 use std::str::FromStr;
 use std::vec::Vec;
 use ya_advent_lib::coords::Coord3D;
 use ya_advent_lib::read::read_input;
+extern crate advent2019;
+use advent2019::nbody::{Body, Gravity, SeriesFormat, System};
 
 #[derive(Copy, Clone)]
 struct Moon {
     pos: Coord3D,
-    initial_pos: Coord3D,
     vel: Coord3D,
 }
 
@@ -26,7 +26,6 @@ impl FromStr for Moon {
             let z: i64 = caps.get(3).unwrap().as_str().parse::<i64>().unwrap();
             Ok(Moon {
                 pos: Coord3D::new(x, y, z),
-                initial_pos: Coord3D::new(x, y, z),
                 vel: Coord3D::new(0, 0, 0),
             })
         } else {
@@ -41,40 +40,23 @@ impl Moon {
     }
 }
 
+fn system(moons: &[Moon]) -> System<3> {
+    let bodies = moons
+        .iter()
+        .map(|m| Body {
+            pos: [m.pos.x, m.pos.y, m.pos.z],
+            vel: [m.vel.x, m.vel.y, m.vel.z],
+        })
+        .collect();
+    System::new(bodies, Gravity::default())
+}
+
 fn simulate(moons: &mut [Moon], steps: usize) {
-    for _ in 0..steps {
-        let mut dv = vec![Coord3D::new(0, 0, 0); moons.len()];
-        moons
-            .iter()
-            .enumerate()
-            .tuple_combinations()
-            .for_each(|((a_ix, a), (b_ix, b))| {
-                if a.pos.x < b.pos.x {
-                    dv[a_ix].x += 1;
-                    dv[b_ix].x -= 1;
-                } else if a.pos.x > b.pos.x {
-                    dv[a_ix].x -= 1;
-                    dv[b_ix].x += 1;
-                }
-                if a.pos.y < b.pos.y {
-                    dv[a_ix].y += 1;
-                    dv[b_ix].y -= 1;
-                } else if a.pos.y > b.pos.y {
-                    dv[a_ix].y -= 1;
-                    dv[b_ix].y += 1;
-                }
-                if a.pos.z < b.pos.z {
-                    dv[a_ix].z += 1;
-                    dv[b_ix].z -= 1;
-                } else if a.pos.z > b.pos.z {
-                    dv[a_ix].z -= 1;
-                    dv[b_ix].z += 1;
-                }
-            });
-        moons.iter_mut().enumerate().for_each(|(idx, m)| {
-            m.vel += dv[idx];
-            m.pos += m.vel;
-        });
+    let mut sys = system(moons);
+    sys.run(steps);
+    for (m, b) in moons.iter_mut().zip(sys.bodies) {
+        m.pos = Coord3D::new(b.pos[0], b.pos[1], b.pos[2]);
+        m.vel = Coord3D::new(b.vel[0], b.vel[1], b.vel[2]);
     }
 }
 
@@ -85,56 +67,28 @@ fn part1(input: &[Moon]) -> i64 {
 }
 
 fn find_repeat(input: &[Moon]) -> usize {
-    let mut moons: Vec<Moon> = input.to_vec();
-    let mut xrepeat = 0usize;
-    let mut yrepeat = 0usize;
-    let mut zrepeat = 0usize;
-
-    for step in 1.. {
-        simulate(&mut moons, 1);
-        let (xr, yr, zr) = moons.iter().fold((0, 0, 0), |(xr, yr, zr), moon| {
-            (
-                if moon.pos.x == moon.initial_pos.x && moon.vel.x == 0 {
-                    xr + 1
-                } else {
-                    xr
-                },
-                if moon.pos.y == moon.initial_pos.y && moon.vel.y == 0 {
-                    yr + 1
-                } else {
-                    yr
-                },
-                if moon.pos.z == moon.initial_pos.z && moon.vel.z == 0 {
-                    zr + 1
-                } else {
-                    zr
-                },
-            )
-        });
-        if xrepeat == 0 && xr == moons.len() {
-            xrepeat = step;
-        }
-        if yrepeat == 0 && yr == moons.len() {
-            yrepeat = step;
-        }
-        if zrepeat == 0 && zr == moons.len() {
-            zrepeat = step;
-        }
-        if xrepeat > 0 && yrepeat > 0 && zrepeat > 0 {
-            break;
-        }
-    }
-    lcm(lcm(xrepeat, yrepeat), zrepeat)
+    system(input).cycle(usize::MAX).unwrap().period
 }
 
 fn part2(input: &[Moon]) -> usize {
     find_repeat(input)
 }
 
+// usage: day12 [csv|jsonl [steps]]
+//
+// With a format, also writes the moons' positions, velocities and energy
+// at each step (1000 by default) to stdout.
 fn main() {
     let input: Vec<Moon> = read_input();
     println!("Part 1: {}", part1(&input));
     println!("Part 2: {}", part2(&input));
+    if let Some(format) = std::env::args().nth(1) {
+        let format: SeriesFormat = format.parse().unwrap();
+        let steps = std::env::args().nth(2).map_or(1000, |s| s.parse().unwrap());
+        system(&input)
+            .export(&mut io::stdout().lock(), format, steps)
+            .unwrap();
+    }
 }
 
 #[cfg(test)]
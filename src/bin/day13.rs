@@ -1,62 +1,576 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
 use advent2019::intcode::{IntcodeVM, ProgMem, RunErr};
+use advent2019::pnm::{create_in, sampled_frames, write_ppm_frames};
 
-fn part1(input: &ProgMem) -> usize {
-    let mut vm = IntcodeVM::with_mem(input);
-    let mut outp = Vec::new();
-    vm.run_with_cb(&mut || None, &mut |v| outp.push(v)).unwrap();
-    outp.chunks(3).filter(|v| v[2] == 2).count()
-}
-
-fn part2(input: &ProgMem) -> i64 {
-    let mut vm = IntcodeVM::with_mem(input);
-    let mut score = 0;
-    let mut paddle_pos = 0;
-    let mut ball_pos = 0;
-    vm.mem[0] = 2;
-    let mut done = false;
-    let mut outp = Vec::new();
-    while !done {
-        outp.clear();
-        match vm.run_with_cb(&mut || None, &mut |v| outp.push(v)) {
-            Ok(_) => {
-                done = true;
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum Tile {
+    #[default]
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl TryFrom<i64> for Tile {
+    type Error = RunErr;
+    fn try_from(v: i64) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(Tile::Empty),
+            1 => Ok(Tile::Wall),
+            2 => Ok(Tile::Block),
+            3 => Ok(Tile::Paddle),
+            4 => Ok(Tile::Ball),
+            _ => Err(RunErr::InvalidInput(format!("tile id {v}"))),
+        }
+    }
+}
+
+impl Tile {
+    fn glyph(&self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '*',
+            Tile::Paddle => '-',
+            Tile::Ball => 'o',
+        }
+    }
+
+    fn colour(&self) -> [u8; 3] {
+        match self {
+            Tile::Empty => [0, 0, 0],
+            Tile::Wall => [128, 128, 128],
+            Tile::Block => [64, 160, 255],
+            Tile::Paddle => [255, 255, 255],
+            Tile::Ball => [255, 64, 64],
+        }
+    }
+}
+
+/// The cabinet's screen, grown to fit whatever has been drawn on it, and
+/// the score display.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct ArcadeScreen {
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
+    score: i64,
+    ball: Option<(usize, usize)>,
+    paddle: Option<(usize, usize)>,
+}
+
+impl ArcadeScreen {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn get(&self, x: usize, y: usize) -> Tile {
+        if x < self.width && y < self.height {
+            self.tiles[y * self.width + x]
+        } else {
+            Tile::Empty
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, tile: Tile) {
+        if x >= self.width || y >= self.height {
+            let width = self.width.max(x + 1);
+            let height = self.height.max(y + 1);
+            let mut tiles = vec![Tile::Empty; width * height];
+            for (row, old) in self.tiles.chunks(self.width.max(1)).enumerate() {
+                tiles[row * width..row * width + old.len()].copy_from_slice(old);
             }
+            (self.width, self.height, self.tiles) = (width, height, tiles);
+        }
+        self.tiles[y * self.width + x] = tile;
+        match tile {
+            Tile::Ball => self.ball = Some((x, y)),
+            Tile::Paddle => self.paddle = Some((x, y)),
+            _ => {}
+        }
+    }
+
+    /// Applies one `(x, y, tile)` instruction; `(-1, 0, n)` sets the score.
+    fn apply(&mut self, x: i64, y: i64, v: i64) -> Result<(), RunErr> {
+        if (x, y) == (-1, 0) {
+            self.score = v;
+            return Ok(());
+        }
+        if x < 0 || y < 0 {
+            return Err(RunErr::InvalidInput(format!("draw at ({x}, {y})")));
+        }
+        self.set(x as usize, y as usize, v.try_into()?);
+        Ok(())
+    }
+
+    /// Applies a batch of output, which must be whole triples.
+    fn apply_all(&mut self, outp: &[i64]) -> Result<(), RunErr> {
+        if !outp.len().is_multiple_of(3) {
+            return Err(RunErr::InvalidInput(format!(
+                "{} values of output",
+                outp.len()
+            )));
+        }
+        outp.chunks(3)
+            .try_for_each(|v| self.apply(v[0], v[1], v[2]))
+    }
+
+    fn count(&self, tile: Tile) -> usize {
+        self.tiles.iter().filter(|t| **t == tile).count()
+    }
+
+    fn pixels(&self) -> Vec<[u8; 3]> {
+        self.tiles.iter().map(|t| t.colour()).collect()
+    }
+}
+
+impl fmt::Display for ArcadeScreen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Score: {}", self.score)?;
+        for y in 0..self.height {
+            let row: String = (0..self.width).map(|x| self.get(x, y).glyph()).collect();
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// The game program and its screen.
+struct Cabinet {
+    vm: IntcodeVM,
+    screen: ArcadeScreen,
+    halted: bool,
+}
+
+impl Cabinet {
+    /// With `free_play`, the cabinet is set to not need quarters.
+    fn new(prog: &ProgMem, free_play: bool) -> Self {
+        let mut vm = IntcodeVM::with_mem(prog);
+        if free_play {
+            vm.mem[0] = 2;
+        }
+        Self {
+            vm,
+            screen: ArcadeScreen::new(),
+            halted: false,
+        }
+    }
+
+    /// Runs the game, with `joystick` as the next input if there is one,
+    /// until it asks for more input or halts.
+    fn advance(&mut self, joystick: Option<i64>) -> Result<(), RunErr> {
+        self.vm.input_queue.extend(joystick);
+        let mut outp = Vec::new();
+        match self.vm.run_with_cb(&mut || None, &mut |v| outp.push(v)) {
+            Ok(_) => self.halted = true,
             Err(RunErr::InputNeeded) => {}
-            Err(_) => {
-                panic!();
-            }
+            Err(e) => return Err(e),
         }
-        outp.chunks(3).for_each(|v| {
-            if v[0] == -1 && v[1] == 0 {
-                score = v[2];
-            } else {
-                match v[2] {
-                    3 => {
-                        paddle_pos = v[0];
+        self.screen.apply_all(&outp)
+    }
+}
+
+/// Decides where to hold the joystick (-1 left, 0 neutral, 1 right) each
+/// time the game wants input.
+trait Strategy {
+    fn joystick(&mut self, screen: &ArcadeScreen) -> i64;
+}
+
+impl<F: FnMut(&ArcadeScreen) -> i64> Strategy for F {
+    fn joystick(&mut self, screen: &ArcadeScreen) -> i64 {
+        self(screen)
+    }
+}
+
+// Keeps the paddle under the ball
+fn follow_ball(screen: &ArcadeScreen) -> i64 {
+    match (screen.paddle, screen.ball) {
+        (Some((paddle, _)), Some((ball, _))) => (ball as i64 - paddle as i64).signum(),
+        _ => 0,
+    }
+}
+
+/// Heads for the column where the ball will next come down to the paddle,
+/// working out from its last two positions how it will bounce off walls
+/// and blocks on the way. Until then, it follows the ball.
+#[derive(Default)]
+struct PredictLanding {
+    last_ball: Option<(usize, usize)>,
+}
+
+impl PredictLanding {
+    fn landing(&self, screen: &ArcadeScreen) -> Option<usize> {
+        let (bx, by) = screen.ball?;
+        let (lx, ly) = self.last_ball?;
+        let (_, py) = screen.paddle?;
+        let (mut dx, mut dy) = (bx as i64 - lx as i64, by as i64 - ly as i64);
+        if dx.abs() != 1 || dy.abs() != 1 {
+            return None;
+        }
+        // blocks disappear once the ball bounces off them
+        let mut hit = HashSet::new();
+        let solid = |(x, y): (i64, i64), hit: &HashSet<(i64, i64)>| {
+            x < 0
+                || y < 0
+                || match screen.get(x as usize, y as usize) {
+                    Tile::Wall => true,
+                    Tile::Block => !hit.contains(&(x, y)),
+                    _ => false,
+                }
+        };
+        let (mut x, mut y) = (bx as i64, by as i64);
+        // the ball can't go round for longer than this without repeating
+        for _ in 0..4 * screen.width * screen.height {
+            if y + 1 == py as i64 && dy > 0 {
+                return Some(x as usize);
+            }
+            let side = (x + dx, y);
+            let above = (x, y + dy);
+            let corner = (x + dx, y + dy);
+            match (solid(side, &hit), solid(above, &hit)) {
+                (false, false) if solid(corner, &hit) => {
+                    hit.insert(corner);
+                    (dx, dy) = (-dx, -dy);
+                }
+                (side_hit, above_hit) => {
+                    if side_hit {
+                        hit.insert(side);
+                        dx = -dx;
                     }
-                    4 => {
-                        ball_pos = v[0];
+                    if above_hit {
+                        hit.insert(above);
+                        dy = -dy;
                     }
-                    _ => {}
-                };
+                }
             }
+            if !solid((x + dx, y + dy), &hit) {
+                (x, y) = (x + dx, y + dy);
+            }
+        }
+        None
+    }
+}
+
+impl Strategy for PredictLanding {
+    fn joystick(&mut self, screen: &ArcadeScreen) -> i64 {
+        let target = self.landing(screen);
+        self.last_ball = screen.ball;
+        match (target, screen.paddle) {
+            (Some(x), Some((paddle, _))) => (x as i64 - paddle as i64).signum(),
+            _ => follow_ball(screen),
+        }
+    }
+}
+
+/// Plays back recorded inputs, then holds still.
+struct Replay {
+    tape: std::vec::IntoIter<i64>,
+}
+
+impl Replay {
+    fn new(tape: Vec<i64>) -> Self {
+        Self {
+            tape: tape.into_iter(),
+        }
+    }
+}
+
+impl Strategy for Replay {
+    fn joystick(&mut self, _: &ArcadeScreen) -> i64 {
+        self.tape.next().unwrap_or(0)
+    }
+}
+
+/// How a game went.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct Metrics {
+    /// Times the game asked for input
+    frames: usize,
+    /// Inputs other than holding the joystick still
+    moves: usize,
+    score: i64,
+    blocks_left: usize,
+}
+
+impl Metrics {
+    fn won(&self) -> bool {
+        self.blocks_left == 0
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} with score {} after {} frames and {} moves",
+            if self.won() { "won" } else { "lost" },
+            self.score,
+            self.frames,
+            self.moves
+        )?;
+        if !self.won() {
+            write!(f, ", {} blocks left", self.blocks_left)?;
+        }
+        Ok(())
+    }
+}
+
+// Plays a game on free play, passing each frame and the input given for it
+// to `on_frame`.
+fn play<S, F>(
+    prog: &ProgMem,
+    mut strategy: S,
+    mut on_frame: F,
+) -> Result<(Metrics, ArcadeScreen), RunErr>
+where
+    S: Strategy,
+    F: FnMut(&ArcadeScreen, i64),
+{
+    let mut cabinet = Cabinet::new(prog, true);
+    let mut metrics = Metrics::default();
+    cabinet.advance(None)?;
+    while !cabinet.halted {
+        let input = strategy.joystick(&cabinet.screen);
+        on_frame(&cabinet.screen, input);
+        metrics.frames += 1;
+        metrics.moves += usize::from(input != 0);
+        cabinet.advance(Some(input))?;
+    }
+    metrics.score = cabinet.screen.score;
+    metrics.blocks_left = cabinet.screen.count(Tile::Block);
+    Ok((metrics, cabinet.screen))
+}
+
+/// Plays a game without keeping any frames, which is as fast as it gets.
+fn play_fast<S: Strategy>(prog: &ProgMem, strategy: S) -> Result<Metrics, RunErr> {
+    play(prog, strategy, |_, _| {}).map(|(metrics, _)| metrics)
+}
+
+/// A whole game: the screen each time the game asked for input, the
+/// joystick position given in reply, and the screen once it halted.
+struct Recording {
+    frames: Vec<ArcadeScreen>,
+    inputs: Vec<i64>,
+    last: ArcadeScreen,
+    metrics: Metrics,
+}
+
+impl Recording {
+    fn play<S: Strategy>(prog: &ProgMem, strategy: S) -> Result<Self, RunErr> {
+        let mut frames = Vec::new();
+        let mut inputs = Vec::new();
+        let (metrics, last) = play(prog, strategy, |screen, input| {
+            frames.push(screen.clone());
+            inputs.push(input);
+        })?;
+        Ok(Self {
+            frames,
+            inputs,
+            last,
+            metrics,
+        })
+    }
+
+    fn screens(&self) -> impl Iterator<Item = &ArcadeScreen> {
+        self.frames.iter().chain([&self.last])
+    }
+
+    fn write_text<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (n, screen) in self.screens().enumerate() {
+            match self.inputs.get(n) {
+                Some(input) => writeln!(out, "Frame {n}, joystick {input}")?,
+                None => writeln!(out, "Frame {n}, game over")?,
+            }
+            writeln!(out, "{screen}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes an animation of the game, keeping one frame in `every`.
+    fn write_images<W: Write>(&self, out: &mut W, every: usize, scale: usize) -> io::Result<()> {
+        let width = self.last.width;
+        let screens: Vec<&ArcadeScreen> = self.screens().collect();
+        let frames = sampled_frames(screens.len(), every).map(|n| {
+            // the screen is normally drawn in full before the first input,
+            // but pad any frame that is smaller
+            let screen = screens[n];
+            let mut pixels = vec![[0; 3]; width * self.last.height];
+            for (y, row) in screen.pixels().chunks(screen.width.max(1)).enumerate() {
+                pixels[y * width..y * width + row.len()].copy_from_slice(row);
+            }
+            pixels
         });
-        vm.input_queue.push_back(if paddle_pos < ball_pos {
-            1
-        } else if paddle_pos > ball_pos {
-            -1
-        } else {
-            0
-        });
+        write_ppm_frames(out, width, frames, scale)
     }
-    score
 }
 
+fn export(rec: &Recording, dir: &Path) -> io::Result<()> {
+    rec.write_text(&mut create_in(dir, "day13_frames.txt")?)?;
+    rec.write_images(&mut create_in(dir, "day13_frames.ppm")?, 10, 8)?;
+    let tape: Vec<String> = rec.inputs.iter().map(|v| v.to_string()).collect();
+    writeln!(create_in(dir, "day13_tape.txt")?, "{}", tape.join(","))
+}
+
+fn part1(input: &ProgMem) -> usize {
+    let mut cabinet = Cabinet::new(input, false);
+    cabinet.advance(None).unwrap();
+    cabinet.screen.count(Tile::Block)
+}
+
+fn part2(metrics: &Metrics) -> i64 {
+    metrics.score
+}
+
+/// Plays a game, keeping every frame only if it's to be exported.
+fn play_game<S: Strategy>(
+    input: &ProgMem,
+    strategy: S,
+    record: bool,
+) -> (Metrics, Option<Recording>) {
+    if record {
+        let rec = Recording::play(input, strategy).unwrap();
+        (rec.metrics, Some(rec))
+    } else {
+        (play_fast(input, strategy).unwrap(), None)
+    }
+}
+
+fn report(input: &ProgMem, (metrics, rec): &(Metrics, Option<Recording>), dir: Option<&Path>) {
+    println!("{metrics}");
+    let (Some(rec), Some(dir)) = (rec, dir) else {
+        return;
+    };
+    export(rec, dir).unwrap();
+    let replayed = play_fast(input, Replay::new(rec.inputs.clone())).unwrap();
+    println!("replayed tape: {replayed}");
+}
+
+// usage: day13 [follow|predict] [dir]
+//
+// Plays a game with the given strategy and says how it went. With a
+// directory, the game is also saved there as text, images and a tape of
+// the inputs, which is then replayed.
 fn main() {
     let input: Vec<ProgMem> = read_input();
     println!("Part 1: {}", part1(&input[0]));
-    println!("Part 2: {}", part2(&input[0]));
+    let mut args = std::env::args().skip(1).peekable();
+    let strategy = args
+        .next_if(|a| a == "follow" || a == "predict")
+        .unwrap_or_default();
+    let dir = args.next();
+    let dir = dir.as_deref().map(Path::new);
+    // part 2 follows the ball, so that game is the one reported on unless
+    // another strategy was asked for
+    let follow = play_game(
+        &input[0],
+        follow_ball,
+        dir.is_some() && strategy != "predict",
+    );
+    println!("Part 2: {}", part2(&follow.0));
+    match strategy.as_str() {
+        "predict" => {
+            let game = play_game(&input[0], PredictLanding::default(), dir.is_some());
+            report(&input[0], &game, dir);
+        }
+        "follow" => report(&input[0], &follow, dir),
+        _ if dir.is_some() => report(&input[0], &follow, dir),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day13_test() {
+        let mut screen = ArcadeScreen::new();
+        screen
+            .apply_all(&[1, 2, 3, 6, 5, 4, 0, 0, 1, 2, 0, 1, -1, 0, 42])
+            .unwrap();
+        assert_eq!((screen.width, screen.height), (7, 6));
+        assert_eq!(screen.paddle, Some((1, 2)));
+        assert_eq!(screen.ball, Some((6, 5)));
+        assert_eq!(screen.count(Tile::Wall), 2);
+        assert_eq!(screen.to_string(), "Score: 42\n# #\n\n -\n\n\n      o\n");
+        assert!(screen.apply_all(&[1, 2]).is_err());
+        assert!(screen.apply(0, 0, 5).is_err());
+
+        // after an instruction that is harmless either way once free play
+        // changes it, draws a wall, paddle and ball, waits for the joystick,
+        // moves the ball and then scores whatever the joystick was
+        let prog: ProgMem = "1,60,60,60,104,0,104,0,104,1,104,1,104,1,104,3,104,2,104,0,104,4,\
+                             3,50,104,2,104,0,104,0,104,3,104,1,104,4,\
+                             104,-1,104,0,4,50,99"
+            .parse()
+            .unwrap();
+        let mut seen = Vec::new();
+        let rec = Recording::play(&prog, |screen: &ArcadeScreen| {
+            seen.push(screen.ball);
+            follow_ball(screen)
+        })
+        .unwrap();
+        assert_eq!(seen, [Some((2, 0))]);
+        assert_eq!(rec.inputs, [1]);
+        assert_eq!(rec.frames.len(), 1);
+        assert_eq!(rec.frames[0].to_string(), "Score: 0\n# o\n -\n");
+        assert_eq!(rec.last.to_string(), "Score: 1\n#\n - o\n");
+
+        let mut out = Vec::new();
+        rec.write_text(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Frame 0, joystick 1\nScore: 0\n# o\n -\n\n\
+             Frame 1, game over\nScore: 1\n#\n - o\n\n"
+        );
+        let mut out = Vec::new();
+        rec.write_images(&mut out, 5, 2).unwrap();
+        assert_eq!(out.windows(2).filter(|w| w == b"P6").count(), 2);
+        assert!(out.starts_with(b"P6\n8 4\n255\n"));
+        assert_eq!(
+            rec.metrics,
+            Metrics {
+                frames: 1,
+                moves: 1,
+                score: 1,
+                blocks_left: 0,
+            }
+        );
+        assert!(rec.metrics.won());
+        assert_eq!(play_fast(&prog, Replay::new(rec.inputs)), Ok(rec.metrics));
+        // holding still scores nothing
+        assert_eq!(play_fast(&prog, Replay::new(vec![])).unwrap().score, 0);
+    }
+
+    #[test]
+    fn predict_test() {
+        // a box with the paddle at the bottom
+        let mut screen = ArcadeScreen::new();
+        for i in 0..7 {
+            screen.apply_all(&[0, i, 1, 6, i, 1, i, 0, 1]).unwrap();
+        }
+        screen.apply_all(&[3, 7, 3, 2, 2, 4]).unwrap();
+        let mut strategy = PredictLanding::default();
+        assert_eq!(strategy.joystick(&screen), -1);
+        // heading down and right, it bounces off the right wall at (5, 5)
+        // and comes down at (4, 6), where the ball-follower would miss it
+        screen.apply_all(&[2, 2, 0, 3, 3, 4]).unwrap();
+        assert_eq!(strategy.landing(&screen), Some(4));
+        assert_eq!(follow_ball(&screen), 0);
+        // with a block in the way it goes back left, off the left wall
+        screen.apply(4, 3, 2).unwrap();
+        assert_eq!(strategy.landing(&screen), Some(2));
+        assert_eq!(strategy.joystick(&screen), -1);
+        // heading up, it bounces between the top wall and the block, then
+        // off the left wall, before coming down
+        screen.apply_all(&[3, 3, 0, 4, 2, 4]).unwrap();
+        assert_eq!(strategy.landing(&screen), Some(4));
+    }
 }
@@ -1,4 +1,5 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
@@ -41,114 +42,349 @@ impl FromStr for Reaction {
     }
 }
 
-fn mk_table(input: &[Reaction]) -> HashMap<&str, &Reaction> {
-    input.iter().map(|i| (i.product.name.as_str(), i)).collect()
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum GraphError {
+    DuplicateProducer(String),
+    Unproduced(String),
+    RawProduced(String),
+    Cycle(Vec<String>),
 }
 
-fn search(table: &HashMap<&str, &Reaction>, n_fuel: usize) -> usize {
-    let mut queue = VecDeque::new();
-    let target = Chem {
-        name: "FUEL".to_string(),
-        qty: n_fuel,
-    };
-    queue.push_back(target);
-    let mut n_ore = 0;
-    let mut inventory: HashMap<String, usize> = HashMap::new();
-
-    while let Some(target) = queue.pop_front() {
-        let mut needed = target.qty;
-        inventory.entry(target.name.clone()).and_modify(|e| {
-            if *e >= needed {
-                *e -= needed;
-                needed = 0;
-            } else {
-                needed -= *e;
-                *e = 0;
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::DuplicateProducer(chem) => {
+                write!(f, "{chem} is produced by more than one reaction")
             }
-        });
-        if needed == 0 {
-            continue;
-        }
-        let reaction = table[target.name.as_str()];
-        let num_per_batch = reaction.product.qty;
-        let num_batches = (needed + num_per_batch - 1) / num_per_batch;
-        let num_produced = num_batches * num_per_batch;
-        inventory
-            .entry(target.name.clone())
-            .and_modify(|e| *e += num_produced - needed)
-            .or_insert(num_produced - needed);
-        for chem in &reaction.ingredients {
-            let num_required = chem.qty * num_batches;
-            if chem.name == "ORE" {
-                n_ore += num_required;
-            } else {
-                queue.push_back(Chem {
-                    name: chem.name.clone(),
-                    qty: num_required,
-                });
+            GraphError::Unproduced(chem) => write!(f, "nothing produces {chem}"),
+            GraphError::RawProduced(chem) => write!(f, "raw material {chem} is produced"),
+            GraphError::Cycle(chems) => write!(f, "reaction cycle among {}", chems.join(", ")),
+        }
+    }
+}
+
+/// Reactions checked to make sense together: each chemical other than the
+/// raw materials is produced by exactly one reaction, and none of them
+/// (however indirectly) need their own product.
+struct ReactionGraph {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    reactions: Vec<Reaction>,
+    producer: Vec<Option<usize>>,
+    // every chemical before all of its ingredients
+    order: Vec<usize>,
+}
+
+impl ReactionGraph {
+    fn new(reactions: &[Reaction]) -> Result<Self, Vec<GraphError>> {
+        Self::with_raws(reactions, &["ORE"])
+    }
+
+    fn with_raws(reactions: &[Reaction], raws: &[&str]) -> Result<Self, Vec<GraphError>> {
+        let mut names: Vec<String> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut id = |name: &str| {
+            *index.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                names.len() - 1
+            })
+        };
+        for name in raws {
+            id(name);
+        }
+        for r in reactions {
+            id(&r.product.name);
+            r.ingredients.iter().for_each(|c| {
+                id(&c.name);
+            });
+        }
+        let mut errors = Vec::new();
+        let mut producer = vec![None; names.len()];
+        for (i, r) in reactions.iter().enumerate() {
+            let chem = index[&r.product.name];
+            if raws.contains(&r.product.name.as_str()) {
+                errors.push(GraphError::RawProduced(r.product.name.clone()));
+            } else if producer[chem].replace(i).is_some() {
+                let err = GraphError::DuplicateProducer(r.product.name.clone());
+                if !errors.contains(&err) {
+                    errors.push(err);
+                }
+            }
+        }
+        for (chem, name) in names.iter().enumerate() {
+            if producer[chem].is_none() && !raws.contains(&name.as_str()) {
+                errors.push(GraphError::Unproduced(name.clone()));
+            }
+        }
+        if !index.contains_key("FUEL") {
+            errors.push(GraphError::Unproduced("FUEL".to_string()));
+        }
+
+        // Kahn's algorithm, counting for each chemical the reactions that
+        // consume it
+        let ingredients = |chem: usize| {
+            producer[chem]
+                .into_iter()
+                .flat_map(|r| reactions[r].ingredients.iter().map(|c| index[&c.name]))
+        };
+        let mut consumers = vec![0; names.len()];
+        (0..names.len())
+            .flat_map(ingredients)
+            .for_each(|c| consumers[c] += 1);
+        let mut ready: Vec<usize> = (0..names.len()).filter(|c| consumers[*c] == 0).collect();
+        let mut order = Vec::with_capacity(names.len());
+        while let Some(chem) = ready.pop() {
+            order.push(chem);
+            for c in ingredients(chem) {
+                consumers[c] -= 1;
+                if consumers[c] == 0 {
+                    ready.push(c);
+                }
+            }
+        }
+        if order.len() < names.len() {
+            // what's left is cycles and the chemicals they need; peel off
+            // the latter, which don't lead back into what's left
+            let mut left: Vec<bool> = consumers.iter().map(|n| *n > 0).collect();
+            while let Some(c) =
+                (0..names.len()).find(|c| left[*c] && !ingredients(*c).any(|i| left[i]))
+            {
+                left[c] = false;
             }
+            let mut cycle: Vec<String> = (0..names.len())
+                .filter(|c| left[*c])
+                .map(|c| names[c].clone())
+                .collect();
+            cycle.sort();
+            errors.push(GraphError::Cycle(cycle));
         }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self {
+            names,
+            index,
+            reactions: reactions.to_vec(),
+            producer,
+            order,
+        })
     }
-    n_ore
-}
-
-fn part1(input: &[Reaction]) -> usize {
-    let table = mk_table(input);
-    search(&table, 1)
-}
-
-fn part2(input: &[Reaction]) -> usize {
-    let table = mk_table(input);
-    let target = 1000000000000;
-    let mut est = target / (search(&table, 10000) / 1000);
-    let interval = est / 10;
-    let mut lowerbound = 0;
-    let mut upperbound = 0;
-    loop {
-        let ore = search(&table, est);
-        if ore == target {
-            return est;
-        }
-        if ore < target {
-            if lowerbound == 0 {
-                lowerbound = est;
-                est = if upperbound > 0 {
-                    lowerbound + (upperbound - lowerbound) / 2
-                } else {
-                    est + interval
-                };
-            } else if upperbound == 0 {
-                est += interval;
+
+    /// How much of each chemical is needed (from any source) to make `qty`
+    /// of `target`, in one pass: by the time a chemical is reached, all
+    /// the reactions that consume it have been counted.
+    fn needs(&self, target: &str, qty: usize) -> Vec<usize> {
+        let mut need = vec![0; self.names.len()];
+        need[self.index[target]] = qty;
+        for &chem in &self.order {
+            let Some(r) = self.producer[chem] else {
+                continue;
+            };
+            let reaction = &self.reactions[r];
+            let batches = need[chem].div_ceil(reaction.product.qty);
+            for c in &reaction.ingredients {
+                need[self.index[&c.name]] += c.qty * batches;
+            }
+        }
+        need
+    }
+
+    /// The raw materials used to make `qty` of `target`.
+    fn raw_needed(&self, target: &str, qty: usize) -> Vec<(&str, usize)> {
+        let need = self.needs(target, qty);
+        (0..self.names.len())
+            .filter(|c| self.producer[*c].is_none())
+            .map(|c| (self.names[c].as_str(), need[c]))
+            .collect()
+    }
+
+    fn ore_for(&self, fuel: usize) -> usize {
+        self.raw_needed("FUEL", fuel)
+            .into_iter()
+            .find(|(name, _)| *name == "ORE")
+            .map_or(0, |(_, qty)| qty)
+    }
+
+    /// The most fuel that `ore` will make, or None if fuel doesn't need any.
+    /// More fuel never takes less ore, so this is a binary search, once an
+    /// amount that is too much has been found.
+    fn max_fuel(&self, ore: usize) -> Option<usize> {
+        if self.ore_for(1) == 0 {
+            return None;
+        }
+        let mut hi = 1;
+        while self.ore_for(hi) <= ore {
+            hi *= 2;
+        }
+        // ore_for(lo) <= ore < ore_for(hi)
+        let mut lo = 0;
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if self.ore_for(mid) <= ore {
+                lo = mid;
             } else {
-                lowerbound = est;
-                est = lowerbound + (upperbound - lowerbound) / 2;
-                if est == lowerbound {
-                    return est;
+                hi = mid;
+            }
+        }
+        Some(lo)
+    }
+
+    /// What every chemical goes through to make `fuel` fuel, with the
+    /// products before their ingredients.
+    fn bill_of_materials(&self, fuel: usize) -> Bom {
+        let need = self.needs("FUEL", fuel);
+        let lines = self
+            .order
+            .iter()
+            .map(|&chem| {
+                let batches = self.producer[chem]
+                    .map(|r| (need[chem].div_ceil(self.reactions[r].product.qty), r));
+                let produced =
+                    batches.map_or(need[chem], |(n, r)| n * self.reactions[r].product.qty);
+                BomLine {
+                    chem: self.names[chem].clone(),
+                    batches: batches.map(|(n, _)| n),
+                    produced,
+                    consumed: need[chem],
+                    leftover: produced - need[chem],
                 }
+            })
+            .collect();
+        Bom { lines }
+    }
+
+    /// Graphviz input with an edge from each ingredient to its product,
+    /// labelled with how much one batch uses. Raw materials are boxes.
+    fn to_dot(&self) -> String {
+        // names are quoted, so only quotes and backslashes need escaping
+        let escaped = |name: &str| name.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph reactions {\n    rankdir=BT;\n");
+        for (chem, name) in self.names.iter().enumerate() {
+            let name = escaped(name);
+            match self.producer[chem] {
+                Some(r) => dot.push_str(&format!(
+                    "    \"{name}\" [label=\"{name}\\n\u{d7}{}\"];\n",
+                    self.reactions[r].product.qty
+                )),
+                None => dot.push_str(&format!("    \"{name}\" [shape=box];\n")),
             }
-        } else if upperbound == 0 {
-            upperbound = est;
-            est = if lowerbound > 0 {
-                lowerbound + (upperbound - lowerbound) / 2
-            } else {
-                est - interval
-            };
-        } else if lowerbound == 0 {
-            est -= interval;
-        } else {
-            upperbound = est;
-            est = lowerbound + (upperbound - lowerbound) / 2;
-            if est == lowerbound {
-                return est;
+        }
+        for r in &self.reactions {
+            for c in &r.ingredients {
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    escaped(&c.name),
+                    escaped(&r.product.name),
+                    c.qty
+                ));
             }
         }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// One chemical's part in making some fuel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct BomLine {
+    chem: String,
+    /// Times its reaction runs, or None for raw materials
+    batches: Option<usize>,
+    produced: usize,
+    /// Used by other reactions, or (for fuel) asked for
+    consumed: usize,
+    leftover: usize,
+}
+
+struct Bom {
+    lines: Vec<BomLine>,
+}
+
+impl Bom {
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("chemical,batches,produced,consumed,leftover\n");
+        for l in &self.lines {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                l.chem,
+                l.batches.map_or(String::new(), |n| n.to_string()),
+                l.produced,
+                l.consumed,
+                l.leftover
+            ));
+        }
+        csv
     }
 }
 
+impl fmt::Display for Bom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.lines.iter().map(|l| l.chem.len()).fold(8, usize::max);
+        writeln!(
+            f,
+            "{:width$} {:>10} {:>14} {:>14} {:>10}",
+            "chemical", "batches", "produced", "consumed", "leftover"
+        )?;
+        for l in &self.lines {
+            writeln!(
+                f,
+                "{:width$} {:>10} {:>14} {:>14} {:>10}",
+                l.chem,
+                l.batches.map_or("raw".to_string(), |n| n.to_string()),
+                l.produced,
+                l.consumed,
+                l.leftover
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn part1(graph: &ReactionGraph) -> usize {
+    graph.ore_for(1)
+}
+
+fn part2(graph: &ReactionGraph) -> usize {
+    graph.max_fuel(1_000_000_000_000).unwrap()
+}
+
+// Each argument is an amount of fuel to show the bill of materials for, or
+// `--csv=N` for the same as CSV. `--dot` prints the reactions as Graphviz
+// input.
+fn query_graph(graph: &ReactionGraph, query: &str) -> Result<(), String> {
+    if query == "--dot" {
+        print!("{}", graph.to_dot());
+        return Ok(());
+    }
+    let (fuel, csv) = match query.strip_prefix("--csv=") {
+        Some(fuel) => (fuel, true),
+        None => (query, false),
+    };
+    let fuel: usize = fuel.parse().map_err(|_| "not an amount of fuel")?;
+    let bom = graph.bill_of_materials(fuel);
+    if csv {
+        print!("{}", bom.to_csv());
+    } else {
+        print!("{bom}");
+    }
+    Ok(())
+}
+
 fn main() {
     let input: Vec<Reaction> = read_input();
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    let graph = match ReactionGraph::new(&input) {
+        Ok(graph) => graph,
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{e}"));
+            std::process::exit(1);
+        }
+    };
+    println!("Part 1: {}", part1(&graph));
+    println!("Part 2: {}", part2(&graph));
+    for query in std::env::args().skip(1) {
+        query_graph(&graph, &query).unwrap_or_else(|e| println!("{query}: {e}"));
+    }
 }
 
 #[cfg(test)]
@@ -167,7 +403,8 @@ mod tests {
 7 A, 1 E => 1 FUEL
 ",
         );
-        assert_eq!(part1(&input), 31);
+        let graph = ReactionGraph::new(&input).unwrap();
+        assert_eq!(part1(&graph), 31);
 
         let input: Vec<Reaction> = test_input(
             "9 ORE => 2 A
@@ -179,7 +416,8 @@ mod tests {
 2 AB, 3 BC, 4 CA => 1 FUEL
 ",
         );
-        assert_eq!(part1(&input), 165);
+        let graph = ReactionGraph::new(&input).unwrap();
+        assert_eq!(part1(&graph), 165);
 
         let input: Vec<Reaction> = test_input(
             "157 ORE => 5 NZVS
@@ -193,8 +431,9 @@ mod tests {
 3 DCFZ, 7 NZVS, 5 HKGWZ, 10 PSHF => 8 KHKGT
 ",
         );
-        assert_eq!(part1(&input), 13312);
-        assert_eq!(part2(&input), 82892753);
+        let graph = ReactionGraph::new(&input).unwrap();
+        assert_eq!(part1(&graph), 13312);
+        assert_eq!(part2(&graph), 82892753);
 
         let input: Vec<Reaction> = test_input(
             "2 VPVL, 7 FWMGM, 2 CXFTF, 11 MNCFX => 1 STKFG
@@ -211,8 +450,9 @@ mod tests {
 176 ORE => 6 VJHF
 ",
         );
-        assert_eq!(part1(&input), 180697);
-        assert_eq!(part2(&input), 5586022);
+        let graph = ReactionGraph::new(&input).unwrap();
+        assert_eq!(part1(&graph), 180697);
+        assert_eq!(part2(&graph), 5586022);
 
         let input: Vec<Reaction> = test_input(
             "171 ORE => 8 CNZTR
@@ -234,7 +474,137 @@ mod tests {
 5 BHXH, 4 VRPVC => 5 LTCX
 ",
         );
-        assert_eq!(part1(&input), 2210736);
-        assert_eq!(part2(&input), 460664);
+        let graph = ReactionGraph::new(&input).unwrap();
+        assert_eq!(part1(&graph), 2210736);
+        assert_eq!(part2(&graph), 460664);
+    }
+
+    #[test]
+    fn graph_test() {
+        let input: Vec<Reaction> = test_input(
+            "10 ORE => 10 A
+1 ORE => 1 B
+7 A, 1 B => 1 C
+7 A, 1 C => 1 D
+7 A, 1 D => 1 E
+7 A, 1 E => 1 FUEL
+",
+        );
+        let graph = ReactionGraph::new(&input).unwrap();
+        assert_eq!(graph.ore_for(0), 0);
+        assert_eq!(graph.ore_for(2), 62);
+        // 28 A for every fuel, made 10 at a time, and 1 B
+        assert_eq!(graph.max_fuel(30), Some(0));
+        assert_eq!(graph.max_fuel(31), Some(1));
+        assert_eq!(graph.max_fuel(62), Some(2));
+        assert_eq!(graph.max_fuel(1000), Some(34));
+        assert_eq!(graph.ore_for(34), 994);
+
+        let input: Vec<Reaction> = test_input(
+            "3 ORE, 1 WATER => 2 A
+2 A, 5 WATER => 1 FUEL
+",
+        );
+        assert_eq!(
+            ReactionGraph::new(&input).err(),
+            Some(vec![GraphError::Unproduced("WATER".to_string())])
+        );
+        let graph = ReactionGraph::with_raws(&input, &["ORE", "WATER"]).unwrap();
+        assert_eq!(graph.raw_needed("FUEL", 3), [("ORE", 9), ("WATER", 18)]);
+        assert_eq!(graph.raw_needed("A", 3), [("ORE", 6), ("WATER", 2)]);
+
+        let input: Vec<Reaction> = test_input(
+            "1 ORE => 1 A
+1 ORE => 2 A
+2 ORE => 3 A
+1 C => 1 B
+1 B, 1 A => 1 C
+1 ORE => 1 ORE
+2 C => 1 FUEL
+",
+        );
+        assert_eq!(
+            ReactionGraph::new(&input).err(),
+            Some(vec![
+                GraphError::DuplicateProducer("A".to_string()),
+                GraphError::RawProduced("ORE".to_string()),
+                GraphError::Cycle(vec!["B".to_string(), "C".to_string()]),
+            ])
+        );
+        assert_eq!(
+            ReactionGraph::new(&input[..1]).err(),
+            Some(vec![GraphError::Unproduced("FUEL".to_string())])
+        );
+    }
+
+    #[test]
+    fn bom_test() {
+        let input: Vec<Reaction> = test_input(
+            "9 ORE => 2 A
+8 ORE => 3 B
+7 ORE => 5 C
+3 A, 4 B => 1 AB
+5 B, 7 C => 1 BC
+4 C, 1 A => 1 CA
+2 AB, 3 BC, 4 CA => 1 FUEL
+",
+        );
+        let graph = ReactionGraph::new(&input).unwrap();
+        let bom = graph.bill_of_materials(1);
+        let line = |chem: &str| bom.lines.iter().find(|l| l.chem == chem).unwrap().clone();
+        assert_eq!(bom.lines[0].chem, "FUEL");
+        assert_eq!(
+            line("A"),
+            BomLine {
+                chem: "A".to_string(),
+                batches: Some(5),
+                produced: 10,
+                consumed: 10,
+                leftover: 0,
+            }
+        );
+        assert_eq!(
+            line("B"),
+            BomLine {
+                chem: "B".to_string(),
+                batches: Some(8),
+                produced: 24,
+                consumed: 23,
+                leftover: 1,
+            }
+        );
+        assert_eq!(line("ORE").batches, None);
+        assert_eq!(line("ORE").produced, 165);
+        // everything consumed went into some batch, or was asked for
+        for l in &bom.lines {
+            let used: usize = input
+                .iter()
+                .flat_map(|r| {
+                    let batches = line(&r.product.name).batches.unwrap();
+                    r.ingredients
+                        .iter()
+                        .filter(|c| c.name == l.chem)
+                        .map(move |c| c.qty * batches)
+                })
+                .sum();
+            assert_eq!(l.consumed, used + usize::from(l.chem == "FUEL"));
+        }
+
+        let csv = bom.to_csv();
+        assert!(csv.starts_with("chemical,batches,produced,consumed,leftover\nFUEL,1,1,1,0\n"));
+        assert!(csv.contains("\nORE,,165,165,0\n"));
+        assert_eq!(bom.to_string().lines().count(), 9);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("    \"ORE\" [shape=box];\n"));
+        assert!(dot.contains("    \"AB\" -> \"FUEL\" [label=\"2\"];\n"));
+        assert!(dot.contains("    \"B\" [label=\"B\\n\u{d7}3\"];\n"));
+        assert_eq!(dot.matches("->").count(), 12);
+
+        // any name the parser accepts makes a valid node
+        let input: Vec<Reaction> = test_input("3 ORE => 1 \"Q\\ X\n2 \"Q\\ X => 1 FUEL\n");
+        let dot = ReactionGraph::new(&input).unwrap().to_dot();
+        assert!(dot.contains("    \"\\\"Q\\\\ X\" -> \"FUEL\" [label=\"2\"];\n"));
+        assert!(dot.contains("[label=\"\\\"Q\\\\ X\\n\u{d7}1\"]"));
     }
 }
@@ -1,4 +1,6 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
 use std::vec::Vec;
 use ya_advent_lib::coords::{CDir, Coord2D};
 use ya_advent_lib::infinite_grid::InfiniteGrid;
@@ -12,6 +14,7 @@ enum MoveResult {
     FoundGoal,
 }
 
+#[derive(Clone)]
 struct Droid {
     vm: IntcodeVM,
 }
@@ -47,99 +50,287 @@ impl Droid {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum Cell {
     Unexplored,
     Wall,
-    Open(usize),
-    Goal(usize),
+    Open,
+    Oxygen,
 }
 
-fn recurse(steps: usize, loc: Coord2D, droid: &mut Droid, grid: &mut InfiniteGrid<Cell>) {
-    for dir in [CDir::N, CDir::E, CDir::W, CDir::S] {
-        let neigh = grid.get_c(loc + dir);
-        match neigh {
-            Cell::Wall => {
-                continue;
-            }
-            Cell::Open(n) if n <= steps + 1 => {
-                continue;
-            }
-            Cell::Goal(n) if n <= steps + 1 => {
-                continue;
-            }
-            _ => {}
+// Where `ch` appears on a drawn map, row by row
+fn marked(input: &[String], ch: char) -> Vec<Coord2D> {
+    input
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.chars()
+                .enumerate()
+                .filter(move |(_, c)| *c == ch)
+                .map(move |(x, _)| Coord2D::new(x as i64, y as i64))
+        })
+        .collect()
+}
+
+/// The area around the droid's starting point, as far as it's known.
+struct ShipMap {
+    grid: InfiniteGrid<Cell>,
+    start: Coord2D,
+    oxygen: Option<Coord2D>,
+}
+
+impl ShipMap {
+    /// Reads a drawn map: `#` walls, `.` open cells, `O` the oxygen system
+    /// and `D` where the droid starts (the origin, if there isn't one).
+    /// `X` marks an open cell that something may block.
+    fn from_input(input: &[String]) -> Self {
+        let grid = InfiniteGrid::from_input(input, Cell::Unexplored, |c, _, _| match c {
+            '.' | 'D' | 'X' => Some(Cell::Open),
+            '#' => Some(Cell::Wall),
+            'O' => Some(Cell::Oxygen),
+            _ => None,
+        });
+        Self {
+            grid,
+            start: marked(input, 'D')
+                .first()
+                .copied()
+                .unwrap_or(Coord2D::new(0, 0)),
+            oxygen: marked(input, 'O').first().copied(),
         }
-        match droid.step(dir) {
-            MoveResult::HitWall => {
-                grid.set_c(loc + dir, Cell::Wall);
-                continue;
-            }
-            MoveResult::Moved => {
-                grid.set_c(loc + dir, Cell::Open(steps + 1));
+    }
+
+    fn open(&self, c: Coord2D) -> bool {
+        matches!(self.grid.get_c(c), Cell::Open | Cell::Oxygen)
+    }
+
+    /// Steps from the nearest of `sources` to every open cell reachable
+    /// from them without passing through `blocked`.
+    fn distances(
+        &self,
+        sources: &[Coord2D],
+        blocked: &HashSet<Coord2D>,
+    ) -> HashMap<Coord2D, usize> {
+        let passable = |c: Coord2D| self.open(c) && !blocked.contains(&c);
+        let mut dist: HashMap<Coord2D, usize> = HashMap::new();
+        let mut queue: VecDeque<Coord2D> = VecDeque::new();
+        for s in sources.iter().filter(|s| passable(**s)) {
+            dist.insert(*s, 0);
+            queue.push_back(*s);
+        }
+        while let Some(pos) = queue.pop_front() {
+            let steps = dist[&pos];
+            for n in pos.neighbors4() {
+                if passable(n) && !dist.contains_key(&n) {
+                    dist.insert(n, steps + 1);
+                    queue.push_back(n);
+                }
             }
-            MoveResult::FoundGoal => {
-                grid.set_c(loc + dir, Cell::Goal(steps + 1));
+        }
+        dist
+    }
+
+    fn shortest_path(&self, from: Coord2D, to: Coord2D) -> Option<usize> {
+        self.distances(&[from], &HashSet::new()).get(&to).copied()
+    }
+
+    /// Spreads oxygen from `sources`, filling every open neighbour of a
+    /// filled cell each minute, except for `blocked` cells.
+    fn simulate_oxygen(&self, sources: &[Coord2D], blocked: &HashSet<Coord2D>) -> OxygenFill {
+        let mut minutes: Vec<Vec<Coord2D>> = Vec::new();
+        for (c, d) in self.distances(sources, blocked) {
+            if minutes.len() <= d {
+                minutes.resize(d + 1, Vec::new());
             }
+            minutes[d].push(c);
         }
-        recurse(steps + 1, loc + dir, droid, grid);
-        droid.step(-dir);
+        minutes
+            .iter_mut()
+            .for_each(|m| m.sort_by_key(|c| (c.y, c.x)));
+        OxygenFill {
+            minutes,
+            blocked: blocked.clone(),
+        }
+    }
+
+    /// Minutes for oxygen to spread from the oxygen system to every open
+    /// cell it can reach.
+    fn fill_time(&self) -> Option<usize> {
+        self.simulate_oxygen(&[self.oxygen?], &HashSet::new())
+            .duration()
+    }
+
+    // Draws the map with `glyph` deciding each cell's character, and any
+    // trailing space trimmed.
+    fn render<F: Fn(Coord2D, Cell) -> char>(&self, glyph: F) -> String {
+        let coords: Vec<(i64, i64)> = self.grid.iter().map(|((x, y), _)| (*x, *y)).collect();
+        let xmin = coords.iter().map(|c| c.0).min().unwrap_or(0);
+        let xmax = coords.iter().map(|c| c.0).max().unwrap_or(-1);
+        let ymin = coords.iter().map(|c| c.1).min().unwrap_or(0);
+        let ymax = coords.iter().map(|c| c.1).max().unwrap_or(-1);
+        let mut out = String::new();
+        for y in ymin..=ymax {
+            let row: String = (xmin..=xmax)
+                .map(|x| glyph(Coord2D::new(x, y), self.grid.get(x, y)))
+                .collect();
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+        out
     }
 }
 
-fn explore(droid: &mut Droid) -> InfiniteGrid<Cell> {
-    let mut grid = InfiniteGrid::new(Cell::Unexplored);
-    let start = Coord2D::new(0, 0);
-    grid.set_c(start, Cell::Open(0));
-    recurse(0, start, droid, &mut grid);
-    grid
+fn cell_glyph(cell: Cell) -> char {
+    match cell {
+        Cell::Unexplored => ' ',
+        Cell::Wall => '#',
+        Cell::Open => '.',
+        Cell::Oxygen => 'O',
+    }
 }
 
-fn part1(input: &ProgMem) -> (usize, InfiniteGrid<Cell>) {
-    let mut droid = Droid::with_mem(input);
-    let grid = explore(&mut droid);
-    let steps = grid
-        .iter()
-        .find_map(|(_, cell)| match cell {
-            Cell::Goal(n) => Some(*n),
-            _ => None,
-        })
-        .unwrap();
-    (steps, grid)
+impl fmt::Display for ShipMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render(|c, cell| match cell {
+            Cell::Open if c == self.start => 'D',
+            _ => cell_glyph(cell),
+        }))
+    }
 }
 
-fn part2(grid: &InfiniteGrid<Cell>) -> usize {
-    let start = grid
-        .iter()
-        .find_map(|((x, y), c)| match c {
-            Cell::Goal(_) => Some(Coord2D::new(*x, *y)),
-            _ => None,
+/// Where oxygen got to, minute by minute.
+struct OxygenFill {
+    /// The cells that filled in each minute, starting with the sources
+    minutes: Vec<Vec<Coord2D>>,
+    blocked: HashSet<Coord2D>,
+}
+
+impl OxygenFill {
+    fn duration(&self) -> Option<usize> {
+        self.minutes.len().checked_sub(1)
+    }
+
+    /// Draws `map` after each minute, with filled cells as `O` and blocked
+    /// ones as `X`.
+    fn frames<'a>(&'a self, map: &'a ShipMap) -> impl Iterator<Item = String> + 'a {
+        let mut filled = HashSet::new();
+        self.minutes.iter().map(move |cells| {
+            filled.extend(cells.iter().copied());
+            map.render(|c, cell| {
+                if filled.contains(&c) {
+                    'O'
+                } else if self.blocked.contains(&c) {
+                    'X'
+                } else {
+                    cell_glyph(cell)
+                }
+            })
         })
-        .unwrap();
-    let mut traversed: HashMap<Coord2D, usize> = HashMap::new();
-    let mut queue: VecDeque<(Coord2D, usize)> = VecDeque::new();
-    queue.push_back((start, 0));
-    traversed.insert(start, 0);
-    while let Some((pos, steps)) = queue.pop_front() {
-        pos.neighbors4()
-            .iter()
-            .filter(|n| matches!(grid.get_c(**n), Cell::Open(_)))
-            .for_each(|n| {
-                if !traversed.contains_key(n) || traversed[n] > steps + 1 {
-                    queue.push_back((*n, steps + 1));
-                    traversed.insert(*n, steps + 1);
+    }
+
+    fn write_frames<W: Write>(&self, out: &mut W, map: &ShipMap) -> io::Result<()> {
+        for (minute, frame) in self.frames(map).enumerate() {
+            writeln!(out, "Minute {minute}\n{frame}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps everywhere the droid can reach, breadth first. Each cell reached
+/// keeps its own copy of the droid, so exploring from it never needs the
+/// droid to find its way back.
+fn explore(droid: Droid) -> ShipMap {
+    let start = Coord2D::new(0, 0);
+    let mut map = ShipMap {
+        grid: InfiniteGrid::new(Cell::Unexplored),
+        start,
+        oxygen: None,
+    };
+    map.grid.set_c(start, Cell::Open);
+    let mut frontier: VecDeque<(Coord2D, Droid)> = VecDeque::from([(start, droid)]);
+    while let Some((pos, droid)) = frontier.pop_front() {
+        for dir in [CDir::N, CDir::E, CDir::S, CDir::W] {
+            let next = pos + dir;
+            if map.grid.get_c(next) != Cell::Unexplored {
+                continue;
+            }
+            let mut droid = droid.clone();
+            let cell = match droid.step(dir) {
+                MoveResult::HitWall => Cell::Wall,
+                MoveResult::Moved => Cell::Open,
+                MoveResult::FoundGoal => {
+                    map.oxygen = Some(next);
+                    Cell::Oxygen
                 }
-            });
+            };
+            map.grid.set_c(next, cell);
+            if cell != Cell::Wall {
+                frontier.push_back((next, droid));
+            }
+        }
     }
-    *traversed.values().max().unwrap()
+    map
 }
 
+fn part1(input: &ProgMem) -> (usize, ShipMap) {
+    let map = explore(Droid::with_mem(input));
+    let steps = map.shortest_path(map.start, map.oxygen.unwrap()).unwrap();
+    (steps, map)
+}
+
+fn part2(map: &ShipMap) -> usize {
+    map.fill_time().unwrap()
+}
+
+// usage: day15 [-m | -f | mapfile]
+//
+// -m also draws the explored map, and -f shows it filling with oxygen. A
+// file with a drawn map (as read by `ShipMap::from_input`) is measured and
+// filled in the same way, with oxygen coming from every `O` on it and never
+// reaching an `X`.
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let arg = match &args[1..] {
+        [] => None,
+        [a] if a == "-m" || a == "-f" || !a.starts_with('-') => Some(a.as_str()),
+        _ => {
+            eprintln!("usage: {} [-m | -f | mapfile]", args[0]);
+            std::process::exit(2);
+        }
+    };
     let input: Vec<ProgMem> = read_input();
-    let (part1, grid) = part1(&input[0]);
+    let (part1, map) = part1(&input[0]);
     println!("Part 1: {part1}");
-    println!("Part 2: {}", part2(&grid));
+    println!("Part 2: {}", part2(&map));
+    match arg {
+        Some("-m") => print!("{map}"),
+        Some("-f") => map
+            .simulate_oxygen(&[map.oxygen.unwrap()], &HashSet::new())
+            .write_frames(&mut io::stdout().lock(), &map)
+            .unwrap(),
+        Some(path) => {
+            let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("{path}: {e}");
+                std::process::exit(1);
+            });
+            let lines: Vec<String> = text.lines().map(String::from).collect();
+            let map = ShipMap::from_input(&lines);
+            match map
+                .oxygen
+                .map(|oxygen| map.shortest_path(map.start, oxygen))
+            {
+                Some(Some(steps)) => println!("{steps} steps to the oxygen system"),
+                Some(None) => println!("the oxygen system can't be reached"),
+                None => println!("no oxygen system"),
+            }
+            let blocked: HashSet<Coord2D> = marked(&lines, 'X').into_iter().collect();
+            let fill = map.simulate_oxygen(&marked(&lines, 'O'), &blocked);
+            fill.write_frames(&mut io::stdout().lock(), &map).unwrap();
+        }
+        None => {}
+    }
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,20 +338,86 @@ mod tests {
 
     #[test]
     fn day15_test() {
-        let input = test_input::<String>(
-            " ##   
-#..## 
-#.#..#
-#.O.# 
- ###  
-",
+        let input = test_input::<String>(include_str!("day15.testinput"));
+        let map = ShipMap::from_input(&input);
+        assert_eq!(part2(&map), 4);
+        assert_eq!(map.oxygen, Some(Coord2D::new(2, 3)));
+        assert_eq!(
+            map.shortest_path(Coord2D::new(1, 1), Coord2D::new(4, 2)),
+            Some(6)
         );
-        let grid = InfiniteGrid::from_input(&input, Cell::Unexplored, |c, _, _| match c {
-            '.' => Some(Cell::Open(0)),
-            '#' => Some(Cell::Wall),
-            'O' => Some(Cell::Goal(0)),
-            _ => None,
-        });
-        assert_eq!(part2(&grid), 4);
+        assert_eq!(
+            map.shortest_path(Coord2D::new(1, 1), Coord2D::new(0, 0)),
+            None
+        );
+        assert_eq!(map.to_string(), " ##\n#..##\n#.#..#\n#.O.#\n ###\n");
+    }
+
+    #[test]
+    fn explore_test() {
+        // a droid that can only go east, once, to find the oxygen system
+        let prog: ProgMem = "3,26,1008,26,4,27,1006,27,21,1005,28,21,1101,0,1,28,\
+                             104,2,1105,1,0,104,0,1105,1,0,0,0,0"
+            .parse()
+            .unwrap();
+        let (steps, map) = part1(&prog);
+        assert_eq!(steps, 1);
+        assert_eq!(part2(&map), 1);
+        assert_eq!(map.to_string(), " ##\n#DO#\n ##\n");
+    }
+
+    #[test]
+    fn oxygen_test() {
+        let input = test_input::<String>(include_str!("day15.testinput"));
+        let map = ShipMap::from_input(&input);
+        let oxygen = map.oxygen.unwrap();
+        let fill = map.simulate_oxygen(&[oxygen], &HashSet::new());
+        let c = |x, y| Coord2D::new(x, y);
+        assert_eq!(
+            fill.minutes,
+            [
+                vec![c(2, 3)],
+                vec![c(1, 3), c(3, 3)],
+                vec![c(1, 2), c(3, 2)],
+                vec![c(1, 1), c(4, 2)],
+                vec![c(2, 1)],
+            ]
+        );
+        let frames: Vec<String> = fill.frames(&map).collect();
+        assert_eq!(frames[2], " ##\n#..##\n#O#O.#\n#OOO#\n ###\n");
+        assert_eq!(frames[4], " ##\n#OO##\n#O#OO#\n#OOO#\n ###\n");
+        let mut out = Vec::new();
+        fill.write_frames(&mut out, &map).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("Minute 0\n ##\n#..##\n#.#..#\n#.O.#\n ###\n\nMinute 1\n"));
+        assert_eq!(out.matches("Minute").count(), 5);
+
+        // a second source at the far end
+        let fill = map.simulate_oxygen(&[oxygen, c(2, 1)], &HashSet::new());
+        assert_eq!(fill.duration(), Some(3));
+        assert_eq!(fill.minutes[3], [c(4, 2)]);
+
+        // blocking the way to the right leaves part of the map empty
+        let blocked = HashSet::from([c(3, 2)]);
+        let fill = map.simulate_oxygen(&[oxygen], &blocked);
+        assert_eq!(fill.duration(), Some(4));
+        assert_eq!(fill.minutes.iter().flatten().count(), 6);
+        assert_eq!(
+            fill.frames(&map).last().unwrap(),
+            " ##\n#OO##\n#O#X.#\n#OOO#\n ###\n"
+        );
+
+        // sources that are walls or blocked don't count
+        assert_eq!(
+            map.simulate_oxygen(&[c(0, 1)], &HashSet::new()).duration(),
+            None
+        );
+        assert_eq!(map.simulate_oxygen(&[c(3, 2)], &blocked).duration(), None);
+
+        // the same map drawn with the blocked cell marked
+        let mut lines = input.clone();
+        lines[2] = lines[2].replace("#..#", "#X.#");
+        assert_eq!(marked(&lines, 'X'), [c(3, 2)]);
+        assert_eq!(ShipMap::from_input(&lines).fill_time(), Some(4));
     }
 }
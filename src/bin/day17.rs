@@ -1,8 +1,11 @@
+use std::fmt;
 use std::vec::Vec;
-use ya_advent_lib::coords::{CDir, Coord2D};
+use ya_advent_lib::coords::{CDir, Coord2D, Turn};
 use ya_advent_lib::grid::Grid;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
+use advent2019::ascii::AsciiTerminal;
+use advent2019::heading::turned;
 use advent2019::intcode::{IntcodeVM, ProgMem};
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -26,14 +29,133 @@ impl From<char> for Cell {
     }
 }
 
-fn part1(input: &ProgMem) -> i64 {
-    let mut vm = IntcodeVM::with_mem(input);
-    let mut outp = String::new();
-    vm.run_with_cb(&mut || None, &mut |v| outp.push(v as u8 as char))
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Cmd {
+    Left,
+    Right,
+    Forward(usize),
+}
+
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cmd::Left => write!(f, "L"),
+            Cmd::Right => write!(f, "R"),
+            Cmd::Forward(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+fn get_grid(input: &ProgMem) -> Grid<Cell> {
+    let mut term = AsciiTerminal::with_mem(input);
+    let outp = term.read_until_prompt().unwrap();
+    //print!("{}", outp.text());
+    Grid::from_input(&outp.lines, Cell::Empty, 0)
+}
+
+// Follows the scaffold from the robot, going straight across intersections
+// and turning only when the scaffold ends ahead, until it dead-ends.
+fn trace_path(grid: &Grid<Cell>) -> Vec<Cmd> {
+    let (mut pos, mut dir) = grid
+        .iter_with_coord()
+        .find_map(|(c, x, y)| match c {
+            Cell::Robot(d) => Some((Coord2D::new(x, y), d)),
+            _ => None,
+        })
         .unwrap();
-    //println!("{outp}");
-    let lines: Vec<String> = outp.lines().map(|l| l.into()).collect();
-    let grid: Grid<Cell> = Grid::from_input(&lines, Cell::Empty, 0);
+    let is_scaffold = |c: Coord2D| grid.get_or_default(c.x, c.y, Cell::Empty) != Cell::Empty;
+    let mut cmds = Vec::new();
+    loop {
+        if is_scaffold(pos + dir) {
+            let mut steps = 0;
+            while is_scaffold(pos + dir) {
+                pos += dir;
+                steps += 1;
+            }
+            cmds.push(Cmd::Forward(steps));
+        } else if is_scaffold(pos + turned(dir, Turn::L)) {
+            dir = turned(dir, Turn::L);
+            cmds.push(Cmd::Left);
+        } else if is_scaffold(pos + turned(dir, Turn::R)) {
+            dir = turned(dir, Turn::R);
+            cmds.push(Cmd::Right);
+        } else {
+            break;
+        }
+    }
+    cmds
+}
+
+fn routine_str<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Splits `cmds` into at most `nfuncs` functions plus a main routine listing
+/// the order to call them in, such that every routine written out as a
+/// comma-separated line is at most `maxlen` characters. Returns the main
+/// routine as indices into the function list.
+fn compress<T>(cmds: &[T], nfuncs: usize, maxlen: usize) -> Option<(Vec<usize>, Vec<Vec<T>>)>
+where
+    T: Clone + PartialEq + fmt::Display,
+{
+    fn recurse<T>(
+        cmds: &[T],
+        nfuncs: usize,
+        maxlen: usize,
+        main: &mut Vec<usize>,
+        funcs: &mut Vec<Vec<T>>,
+    ) -> bool
+    where
+        T: Clone + PartialEq + fmt::Display,
+    {
+        if cmds.is_empty() {
+            return true;
+        }
+        // each call in main is a single letter plus a comma
+        if (main.len() + 1) * 2 - 1 > maxlen {
+            return false;
+        }
+        for f in 0..funcs.len() {
+            if cmds.starts_with(&funcs[f]) {
+                main.push(f);
+                if recurse(&cmds[funcs[f].len()..], nfuncs, maxlen, main, funcs) {
+                    return true;
+                }
+                main.pop();
+            }
+        }
+        if funcs.len() < nfuncs {
+            for len in 1..=cmds.len() {
+                if routine_str(&cmds[..len]).len() > maxlen {
+                    break;
+                }
+                funcs.push(cmds[..len].to_vec());
+                main.push(funcs.len() - 1);
+                if recurse(&cmds[len..], nfuncs, maxlen, main, funcs) {
+                    return true;
+                }
+                main.pop();
+                funcs.pop();
+            }
+        }
+        false
+    }
+
+    let mut main = Vec::new();
+    let mut funcs = Vec::new();
+    if recurse(cmds, nfuncs, maxlen, &mut main, &mut funcs) {
+        Some((main, funcs))
+    } else {
+        None
+    }
+}
+
+fn part1(input: &ProgMem) -> i64 {
+    let grid = get_grid(input);
     grid.iter_with_coord()
         .filter_map(|(c, x, y)| {
             if c == Cell::Scaffold
@@ -50,8 +172,27 @@ fn part1(input: &ProgMem) -> i64 {
         .sum()
 }
 
-fn part2(_input: &ProgMem) -> i64 {
-    0
+fn part2(input: &ProgMem) -> i64 {
+    let path = trace_path(&get_grid(input));
+    let (main, mut funcs) = compress(&path, 3, 20).expect("path does not compress");
+    // the robot always asks for three functions, whether or not they're used
+    while funcs.len() < 3 {
+        funcs.push(funcs[0].clone());
+    }
+    let mut vm = IntcodeVM::with_mem(input);
+    vm.mem[0] = 2;
+    let mut term = AsciiTerminal::new(vm);
+    let main: Vec<char> = main.iter().map(|f| (b'A' + *f as u8) as char).collect();
+    term.send_line(&routine_str(&main)).unwrap();
+    for f in &funcs {
+        term.send_line(&routine_str(f)).unwrap();
+    }
+    term.send_line("n").unwrap();
+    let resp = term.read_until_prompt().unwrap();
+    match resp.values.last() {
+        Some(v) => *v,
+        None => panic!("robot failed:\n{}", resp.text()),
+    }
 }
 
 fn main() {
@@ -59,3 +200,57 @@ fn main() {
     println!("Part 1: {}", part1(&input[0]));
     println!("Part 2: {}", part2(&input[0]));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ya_advent_lib::read::test_input;
+
+    #[test]
+    fn day17_test() {
+        let input: Vec<String> = test_input(
+            "#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#.#.#......
+....#.#.#......
+....#.#.#......
+....#####......
+",
+        );
+        let grid: Grid<Cell> = Grid::from_input(&input, Cell::Empty, 0);
+        let path = trace_path(&grid);
+        assert_eq!(
+            routine_str(&path),
+            "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+
+        let (main, funcs) = compress(&path, 3, 20).unwrap();
+        assert!(funcs.len() <= 3);
+        assert!(main.len() * 2 - 1 <= 20);
+        assert!(funcs.iter().all(|f| routine_str(f).len() <= 20));
+        let expanded: Vec<Cmd> = main.iter().flat_map(|f| funcs[*f].clone()).collect();
+        assert_eq!(expanded, path);
+
+        // too short a limit to fit anything
+        assert!(compress(&path, 3, 4).is_none());
+    }
+
+    #[test]
+    fn compress_test() {
+        let cmds: Vec<char> = "abcabcxyzabcxyz".chars().collect();
+        let (main, funcs) = compress(&cmds, 2, 9).unwrap();
+        assert_eq!(main, [0, 0, 1, 0, 1]);
+        assert_eq!(funcs, [vec!['a', 'b', 'c'], vec!['x', 'y', 'z']]);
+        assert!(compress(&cmds, 1, 9).is_none());
+        assert!(compress(&cmds, 2, 8).is_none());
+    }
+}
@@ -1,8 +1,4 @@
-use itertools::Itertools;
-use num::Zero;
-use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap, HashSet};
-use std::ops::Add;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::vec::Vec;
 use ya_advent_lib::algorithm::dijkstra;
 use ya_advent_lib::coords::Coord2D;
@@ -30,144 +26,151 @@ impl From<char> for Cell {
     }
 }
 
-fn merge_doors(a: &str, b: &str) -> String {
-    a.chars().chain(b.chars()).sorted().dedup().collect()
+fn key_bit(k: char) -> u32 {
+    1 << (k as u8 - b'a')
 }
 
+// A shortest path from a start or key to another key. `keys` holds the keys
+// picked up on the way, which are collected along with the destination.
 struct Edge {
     node_a: char,
     node_b: char,
     steps: usize,
-    doors: String,
+    doors: u32,
+    keys: u32,
 }
 impl Edge {
-    fn can_traverse(&self, keys: &str) -> bool {
-        let doors: HashSet<char> = self.doors.chars().collect();
-        let keys: HashSet<char> = keys.chars().collect();
-        doors.difference(&keys).count() == 0
+    fn can_traverse(&self, keys: u32) -> bool {
+        self.doors & !keys == 0
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
-struct Cost {
-    steps: usize,
-    doors: String,
-}
-impl Cost {
-    fn new(steps: usize, doors: &str) -> Self {
-        Cost {
-            steps,
-            doors: doors.to_owned(),
-        }
-    }
-}
-impl Add for Cost {
-    type Output = Self;
-    fn add(self, other: Self) -> Self {
-        Cost {
-            steps: self.steps + other.steps,
-            doors: merge_doors(&self.doors, &other.doors),
-        }
-    }
-}
-impl Zero for Cost {
-    fn zero() -> Self {
-        Cost {
-            steps: 0,
-            doors: String::new(),
-        }
-    }
-    fn is_zero(&self) -> bool {
-        self.steps == 0 && self.doors.is_empty()
-    }
-}
-
-fn mk_edges(grid: &Grid<Cell>) -> Vec<Edge> {
-    let locs: HashMap<char, Coord2D> =
-        HashMap::from_iter(grid.iter_with_coord().filter_map(|(c, x, y)| match c {
+// Nodes are the keys, plus the starting positions numbered '0', '1', ... in
+// reading order. Returns the start nodes and the edges out of every node.
+fn mk_edges(grid: &Grid<Cell>) -> (Vec<char>, Vec<Edge>) {
+    let mut starts = Vec::new();
+    let locs: Vec<(char, Coord2D)> = grid
+        .iter_with_coord()
+        .filter_map(|(c, x, y)| match c {
             Cell::Key(k) => Some((k, Coord2D::new(x, y))),
-            Cell::Start => Some(('@', Coord2D::new(x, y))),
+            Cell::Start => {
+                let id = (b'0' + starts.len() as u8) as char;
+                starts.push(id);
+                Some((id, Coord2D::new(x, y)))
+            }
             _ => None,
-        }));
-    locs.keys()
-        .tuple_combinations()
-        .filter_map(|(a, b)| {
-            let start = locs[a];
-            let target = locs[b];
-            let mut queue: BinaryHeap<(Reverse<usize>, Coord2D)> = BinaryHeap::new();
-            let mut traversed: HashMap<Coord2D, (usize, String)> = HashMap::new();
-            queue.push((Reverse(0), start));
-            traversed.insert(start, (0, String::new()));
-            while let Some((_, loc)) = queue.pop() {
-                let node = &traversed[&loc];
-                if loc == target {
-                    return Some((node.0, node.1.clone(), *a, *b));
-                }
-                loc.neighbors4()
-                    .into_iter()
-                    .filter_map(|n| match grid.get_c(n) {
-                        Cell::Wall => None,
-                        Cell::Key(k) if k != *b => None,
-                        Cell::Open | Cell::Start | Cell::Key(_) => {
-                            Some((n, node.0 + 1, node.1.clone()))
-                        }
+        })
+        .collect();
+    let edges = locs
+        .iter()
+        .flat_map(|(node, start)| {
+            let mut edges = Vec::new();
+            let mut queue: VecDeque<(Coord2D, usize, u32, u32)> = VecDeque::new();
+            let mut traversed: HashSet<Coord2D> = HashSet::new();
+            queue.push_back((*start, 0, 0, 0));
+            traversed.insert(*start);
+            while let Some((loc, steps, doors, keys)) = queue.pop_front() {
+                for n in loc.neighbors4() {
+                    if !traversed.insert(n) {
+                        continue;
+                    }
+                    match grid.get_c(n) {
+                        Cell::Wall => {}
+                        Cell::Open | Cell::Start => queue.push_back((n, steps + 1, doors, keys)),
                         Cell::Door(d) => {
-                            let doors: String = merge_doors(&node.1, &d.to_string());
-                            Some((n, node.0 + 1, doors))
+                            queue.push_back((n, steps + 1, doors | key_bit(d), keys));
+                        }
+                        Cell::Key(k) => {
+                            edges.push(Edge {
+                                node_a: *node,
+                                node_b: k,
+                                steps: steps + 1,
+                                doors,
+                                keys,
+                            });
+                            queue.push_back((n, steps + 1, doors, keys | key_bit(k)));
                         }
-                    })
-                    .for_each(|_| {
-                        // TODO
-                    });
+                    }
+                }
             }
-            None
-        })
-        .map(|(steps, doors, a, b)| Edge {
-            node_a: a,
-            node_b: b,
-            steps,
-            doors,
+            edges
         })
-        .collect()
+        .collect();
+    (starts, edges)
 }
 
-fn part1(input: &[String]) -> usize {
+// Searches over (robot positions, keys collected) for the fewest total
+// steps to collect every key.
+fn collect_keys(input: &[String]) -> usize {
     let grid = Grid::from_input(input, Cell::Wall, 0);
-    let edges = mk_edges(&grid);
-    println!("{} edges", edges.len());
-    let start: (String, char) = (String::new(), '@');
-    let target: String = grid
+    let (starts, edges) = mk_edges(&grid);
+    let mut adjacent: HashMap<char, Vec<&Edge>> = HashMap::new();
+    for e in &edges {
+        adjacent.entry(e.node_a).or_default().push(e);
+    }
+    let target: u32 = grid
         .iter()
         .filter_map(|c| match c {
-            Cell::Key(k) => Some(k),
+            Cell::Key(k) => Some(key_bit(k)),
             _ => None,
         })
-        .collect();
+        .fold(0, |acc, k| acc | k);
+    let start: (Vec<char>, u32) = (starts, 0);
     dijkstra(
         start,
-        |(keys, _)| *keys == target,
-        |(keys, node)| {
-            edges
+        |(_, keys)| *keys == target,
+        |(robots, keys)| {
+            robots
                 .iter()
-                .filter_map(|e| {
-                    if e.node_a == *node {
-                        Some((e, e.node_b))
-                    } else if e.node_b == *node {
-                        Some((e, e.node_a))
-                    } else {
-                        None
-                    }
+                .enumerate()
+                .flat_map(|(idx, node)| {
+                    adjacent
+                        .get(node)
+                        .into_iter()
+                        .flatten()
+                        .filter(|e| *keys & key_bit(e.node_b) == 0 && e.can_traverse(*keys))
+                        .map(move |e| {
+                            let mut robots = robots.clone();
+                            robots[idx] = e.node_b;
+                            ((robots, *keys | e.keys | key_bit(e.node_b)), e.steps)
+                        })
                 })
-                .filter(|(edge, next)| !keys.contains(*next) && edge.can_traverse(keys))
-                .map(|(edge, next)| ((merge_doors(keys, &String::from(*node)), next), edge.steps))
                 .collect()
         },
     )
     .unwrap()
 }
 
-fn part2(_input: &[String]) -> i64 {
-    0
+// Replaces the 3x3 area around a lone '@' with a wall cross and four starts.
+// Maps that already have several starts are returned as is.
+fn split_vault(input: &[String]) -> Vec<String> {
+    let mut rows: Vec<Vec<char>> = input.iter().map(|l| l.chars().collect()).collect();
+    let starts: Vec<(usize, usize)> = rows
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, c)| **c == '@')
+                .map(move |(x, _)| (x, y))
+        })
+        .collect();
+    if let [(x, y)] = starts[..] {
+        for (dy, row) in ["@#@", "###", "@#@"].iter().enumerate() {
+            for (dx, c) in row.chars().enumerate() {
+                rows[y + dy - 1][x + dx - 1] = c;
+            }
+        }
+    }
+    rows.iter().map(|r| r.iter().collect()).collect()
+}
+
+fn part1(input: &[String]) -> usize {
+    collect_keys(input)
+}
+
+fn part2(input: &[String]) -> usize {
+    collect_keys(&split_vault(input))
 }
 
 fn main() {
@@ -218,4 +221,57 @@ mod tests {
         );
         assert_eq!(part1(&input), 81);
     }
+
+    #[test]
+    fn day18_part2_test() {
+        let input: Vec<String> = test_input(
+            "#######
+#a.#Cd#
+##...##
+##.@.##
+##...##
+#cB#Ab#
+#######
+",
+        );
+        assert_eq!(part2(&input), 8);
+
+        let input: Vec<String> = test_input(
+            "###############
+#d.ABC.#.....a#
+######@#@######
+###############
+######@#@######
+#b.....#.....c#
+###############
+",
+        );
+        assert_eq!(part2(&input), 24);
+
+        let input: Vec<String> = test_input(
+            "#############
+#DcBa.#.GhKl#
+#.###@#@#I###
+#e#d#####j#k#
+###C#@#@###J#
+#fEbA.#.FgHi#
+#############
+",
+        );
+        assert_eq!(part2(&input), 32);
+
+        let input: Vec<String> = test_input(
+            "#############
+#g#f.D#..h#l#
+#F###e#E###.#
+#dCba@#@BcIJ#
+#############
+#nK.L@#@G...#
+#M###N#H###.#
+#o#m..#i#jk.#
+#############
+",
+        );
+        assert_eq!(part2(&input), 72);
+    }
 }
@@ -2,6 +2,7 @@ use itertools::Itertools;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
+use advent2019::batch::{Batch, Job};
 use advent2019::intcode::{IntcodeVM, ProgMem};
 
 fn test_loc(input: &ProgMem, x: i64, y: i64) -> i64 {
@@ -15,9 +16,13 @@ fn test_loc(input: &ProgMem, x: i64, y: i64) -> i64 {
 }
 
 fn part1(input: &ProgMem) -> usize {
-    (0..50)
+    let jobs = (0..50)
         .cartesian_product(0..50)
-        .filter(|(x, y)| test_loc(input, *x, *y) != 0)
+        .map(|(x, y)| Job::new(vec![], vec![x, y]));
+    Batch::new(input)
+        .run_all(jobs)
+        .iter()
+        .filter(|o| o.output[0] != 0)
         .count()
 }
 
@@ -1,36 +1,44 @@
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
-use advent2019::intcode::{IntcodeVM, ProgMem};
+use advent2019::ascii::AsciiTerminal;
+use advent2019::intcode::ProgMem;
 
-fn part1(input: &ProgMem) -> i64 {
-    let mut vm = IntcodeVM::with_mem(input);
+fn run_script(input: &ProgMem, script: &str) -> i64 {
+    let mut term = AsciiTerminal::with_mem(input);
+    term.add_prompt("Input instructions:");
+    let resp = term.read_until_prompt().unwrap();
+    assert!(resp.prompt.is_some());
+    script
+        .lines()
+        .try_for_each(|line| term.send_line(line))
+        .unwrap();
+    let resp = term.read_until_prompt().unwrap();
+    match resp.values.last() {
+        Some(v) => *v,
+        None => panic!("springdroid failed:\n{}", resp.text()),
+    }
+}
 
+fn part1(input: &ProgMem) -> i64 {
     // if any of A B or C are 0 and D is 1, then jump
-    "NOT A T
+    run_script(
+        input,
+        "NOT A T
 NOT B J
 OR T J
 NOT C T
 OR T J
 AND D J
 WALK
-"
-    .chars()
-    .map(|c| c as u8 as i64)
-    .for_each(|c| vm.input_queue.push_back(c));
-
-    let mut out = 0;
-    //vm.run_interactive(&mut |v| {out = v;}).unwrap();
-    vm.run_with_cb(&mut || None, &mut |v| {
-        out = v;
-    })
-    .unwrap();
-    out
+",
+    )
 }
 
 fn part2(input: &ProgMem) -> i64 {
-    let mut vm = IntcodeVM::with_mem(input);
-    "OR B J
+    run_script(
+        input,
+        "OR B J
 AND C J
 NOT J J
 AND D J
@@ -38,17 +46,8 @@ AND H J
 NOT A T
 OR T J
 RUN
-"
-    .chars()
-    .map(|c| c as u8 as i64)
-    .for_each(|c| vm.input_queue.push_back(c));
-
-    let mut out = 0;
-    vm.run_with_cb(&mut || None, &mut |v| {
-        out = v;
-    })
-    .unwrap();
-    out
+",
+    )
 }
 
 fn main() {
@@ -2,11 +2,15 @@ use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Cursor, Read};
+use std::path::Path;
 use std::vec::Vec;
 use ya_advent_lib::coords::CDir;
 use ya_advent_lib::read::read_input;
 extern crate advent2019;
+use advent2019::ascii::AsciiTerminal;
 use advent2019::intcode::{IntcodeVM, ProgMem, RunErr};
+use advent2019::interactive::Session;
 
 lazy_static! {
     static ref ROOM_RE: Regex = Regex::new(r"== (.*) ==").unwrap();
@@ -38,8 +42,14 @@ impl Room {
     }
 }
 
+fn new_terminal(program: &ProgMem) -> AsciiTerminal {
+    let mut term = AsciiTerminal::with_mem(program);
+    term.add_prompt("Command?");
+    term
+}
+
 fn play_game(program: &ProgMem) {
-    let mut vm = IntcodeVM::with_mem(program);
+    let mut term = new_terminal(program);
     let mut rooms: HashMap<String, Room> = HashMap::new();
     let mut bad_items: HashSet<String> = HashSet::from_iter(["infinite loop".to_string()]);
     let mut current_path: Vec<CDir> = Vec::new();
@@ -52,36 +62,33 @@ fn play_game(program: &ProgMem) {
     let mut item_test_iter: Option<Box<dyn Iterator<Item = Vec<String>>>> = None;
     let mut dir_to_test = CDir::N;
     loop {
-        let mut output = String::new();
-        match vm.run_with_cb(&mut || None, &mut |v| output.push(v as u8 as char)) {
-            Ok(_) => {
-                assert!(!last_picked_item.is_empty() || state == State::Test);
-                if V {
-                    print!("{output}");
-                }
-                if state == State::Test {
-                    if !V {
-                        println!("{}", output.lines().last().unwrap());
-                    }
-                    return;
-                }
-                bad_items.insert(last_picked_item);
-                last_picked_item = String::new();
-                // reset
-                vm = IntcodeVM::with_mem(program);
-                rooms = HashMap::new();
-                inventory = HashSet::new();
-                current_path = Vec::new();
-                current_room = String::new();
-                prev_room = String::new();
-                state = State::Exploring;
-                if V {
-                    println!("*** Starting over ***");
+        let response = term.read_until_prompt().unwrap();
+        let output = response.text();
+        if response.halted {
+            assert!(!last_picked_item.is_empty() || state == State::Test);
+            if V {
+                print!("{output}");
+            }
+            if state == State::Test {
+                if !V {
+                    println!("{}", output.lines().last().unwrap());
                 }
-                continue;
+                return;
             }
-            Err(RunErr::InputNeeded) => {}
-            Err(_) => panic!(),
+            bad_items.insert(last_picked_item);
+            last_picked_item = String::new();
+            // reset
+            term = new_terminal(program);
+            rooms = HashMap::new();
+            inventory = HashSet::new();
+            current_path = Vec::new();
+            current_room = String::new();
+            prev_room = String::new();
+            state = State::Exploring;
+            if V {
+                println!("*** Starting over ***");
+            }
+            continue;
         }
 
         if V {
@@ -149,7 +156,7 @@ fn play_game(program: &ProgMem) {
             // reset
             bad_items.insert(last_picked_item);
             last_picked_item = String::new();
-            vm = IntcodeVM::with_mem(program);
+            term = new_terminal(program);
             rooms = HashMap::new();
             inventory = HashSet::new();
             current_path = Vec::new();
@@ -196,18 +203,18 @@ fn play_game(program: &ProgMem) {
                         prev_room = current_room;
                         current_room = String::new();
                         state = State::Exploring;
-                        vm.ascii_input(dir_cmd(*nextdir));
+                        term.send_line(dir_cmd(*nextdir)).unwrap();
                         if V {
-                            print!("{}", dir_cmd(*nextdir));
+                            println!("{}", dir_cmd(*nextdir));
                         }
                     } else if let Some(nextdir) = current_path.last().copied() {
                         current_path.pop();
                         prev_room = current_room;
                         current_room = String::new();
                         state = State::Backtrack;
-                        vm.ascii_input(dir_cmd(-nextdir));
+                        term.send_line(dir_cmd(-nextdir)).unwrap();
                         if V {
-                            print!("{}", dir_cmd(-nextdir));
+                            println!("{}", dir_cmd(-nextdir));
                         }
                     } else {
                         prev_room = String::new();
@@ -223,10 +230,10 @@ fn play_game(program: &ProgMem) {
                     if let Some(item) = current_room_items.pop() {
                         last_picked_item = item.clone();
                         inventory.insert(item.clone());
-                        let cmd = format!("take {item}\n");
-                        vm.ascii_input(&cmd);
+                        let cmd = format!("take {item}");
+                        term.send_line(&cmd).unwrap();
                         if V {
-                            print!("{cmd}");
+                            println!("{cmd}");
                         }
                     } else {
                         state = State::Exploring;
@@ -245,9 +252,9 @@ fn play_game(program: &ProgMem) {
                     }
                     let next = path_to_checkpoint.splice(0..1, []).next().unwrap();
                     current_path.push(next);
-                    vm.ascii_input(dir_cmd(next));
+                    term.send_line(dir_cmd(next)).unwrap();
                     if V {
-                        print!("{}", dir_cmd(next));
+                        println!("{}", dir_cmd(next));
                     }
                     break;
                 }
@@ -255,23 +262,23 @@ fn play_game(program: &ProgMem) {
                     if let Some(carry) = item_test_iter.as_deref_mut().unwrap().next() {
                         let carry: HashSet<String> = HashSet::from_iter(carry);
                         for drop in inventory.difference(&carry) {
-                            let cmd = format!("drop {drop}\n");
-                            vm.ascii_input(&cmd);
+                            let cmd = format!("drop {drop}");
+                            term.send_line(&cmd).unwrap();
                             if V {
-                                print!("{cmd}");
+                                println!("{cmd}");
                             }
                         }
                         for take in carry.difference(&inventory) {
-                            let cmd = format!("take {take}\n");
-                            vm.ascii_input(&cmd);
+                            let cmd = format!("take {take}");
+                            term.send_line(&cmd).unwrap();
                             if V {
-                                print!("{cmd}");
+                                println!("{cmd}");
                             }
                         }
                         inventory = carry.clone();
-                        vm.ascii_input(dir_cmd(dir_to_test));
+                        term.send_line(dir_cmd(dir_to_test)).unwrap();
                         if V {
-                            print!("{}", dir_cmd(dir_to_test));
+                            println!("{}", dir_cmd(dir_to_test));
                         }
                     } else {
                         panic!();
@@ -285,26 +292,57 @@ fn play_game(program: &ProgMem) {
 
 fn dir_cmd(dir: CDir) -> &'static str {
     match dir {
-        CDir::N => "north\n",
-        CDir::E => "east\n",
-        CDir::W => "west\n",
-        CDir::S => "south\n",
+        CDir::N => "north",
+        CDir::E => "east",
+        CDir::W => "west",
+        CDir::S => "south",
     }
 }
 
-#[allow(dead_code)]
-fn play_interactive(input: &ProgMem) -> i64 {
+/// Play by hand. Meta-commands such as `:save`, `:undo` and `:history` are
+/// available; if a transcript path is given, everything typed is saved to it,
+/// and an existing transcript can be replayed with `replay` before handing
+/// control back to the terminal.
+fn play_interactive(input: &ProgMem, transcript: Option<&str>, replay: Option<&str>) {
     let mut vm = IntcodeVM::with_mem(input);
-    let mut out = 0;
-    vm.run_interactive(&mut |v| {
-        out = v;
-    })
-    .unwrap();
-    out
+    // read the replay in full before the transcript is created, as they may
+    // be the same file
+    let replay = replay.map(|path| std::fs::read(path).unwrap());
+    let mut session = match transcript {
+        Some(path) => Session::with_transcript(Path::new(path)).unwrap(),
+        None => Session::new(),
+    };
+    let stdin = std::io::stdin().lock();
+    let mut reader: Box<dyn BufRead> = match replay {
+        Some(bytes) => Box::new(Cursor::new(bytes).chain(stdin)),
+        None => Box::new(stdin),
+    };
+    match session.run(&mut vm, &mut reader, &mut std::io::stdout(), &mut |v| {
+        println!("{v}");
+    }) {
+        Ok(_) | Err(RunErr::EndOfInput) => {}
+        Err(e) => panic!("{e:?}"),
+    }
 }
 
+// usage: day25 [-i [transcript [replay]]]
+//
+// -i plays by hand instead of solving the game, optionally saving what is
+// typed to `transcript` and replaying `replay` first (see `play_interactive`).
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 4 || args.get(1).is_some_and(|a| a != "-i") {
+        eprintln!("usage: {} [-i [transcript [replay]]]", args[0]);
+        std::process::exit(2);
+    }
     let input: Vec<ProgMem> = read_input();
-    // play_interactive(&input[0]);
-    play_game(&input[0]);
+    if args.len() > 1 {
+        play_interactive(
+            &input[0],
+            args.get(2).map(String::as_str),
+            args.get(3).map(String::as_str),
+        );
+    } else {
+        play_game(&input[0]);
+    }
 }
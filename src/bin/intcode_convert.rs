@@ -0,0 +1,44 @@
+use std::fs;
+use std::io::{Read, Write};
+extern crate advent2019;
+use advent2019::intcode::ProgMem;
+use advent2019::progfile::Format;
+
+// Converts an Intcode program between file formats. The input format is
+// detected automatically.
+//
+// usage: intcode_convert <text|annotated|binary|varint> [infile [outfile]]
+//
+// Reads stdin and writes stdout if files aren't given.
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 4 {
+        eprintln!(
+            "usage: {} <text|annotated|binary|varint> [infile [outfile]]",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    let format: Format = args[1].parse().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
+    let bytes = match args.get(2) {
+        Some(path) => fs::read(path).unwrap(),
+        None => {
+            let mut buf = Vec::new();
+            std::io::stdin().read_to_end(&mut buf).unwrap();
+            buf
+        }
+    };
+    let prog = ProgMem::from_bytes(&bytes).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    let out = prog.to_bytes(format);
+    match args.get(3) {
+        Some(path) => fs::write(path, out).unwrap(),
+        None => std::io::stdout().write_all(&out).unwrap(),
+    }
+}
@@ -0,0 +1,9 @@
+use ya_advent_lib::coords::{CDir, Turn};
+
+/// The direction faced after turning from `dir`, for robots that track
+/// their heading as they walk.
+pub fn turned(dir: CDir, turn: Turn) -> CDir {
+    let mut dir = dir;
+    dir += turn;
+    dir
+}
@@ -1,7 +1,11 @@
+use crate::ascii;
+use crate::interactive::Session;
 use std::collections::VecDeque;
+use std::io::{BufRead, Write};
 use std::str::FromStr;
 use std::vec::Vec;
 
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ProgMem(pub Vec<i64>);
 
 impl FromStr for ProgMem {
@@ -25,6 +29,11 @@ pub enum StepResult {
 pub enum RunErr {
     InputNeeded,
     InvalidInstr(String),
+    InvalidInput(String),
+    EndOfInput,
+    Io(String),
+    // the program halted without producing output it was expected to
+    NoOutput(String),
 }
 
 enum Opcode {
@@ -64,6 +73,20 @@ impl Opcode {
             _ => false,
         }
     }
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Mul => "mul",
+            Self::Inp => "in",
+            Self::Out => "out",
+            Self::Jnz => "jnz",
+            Self::Jz => "jz",
+            Self::Lt => "lt",
+            Self::Eq => "eq",
+            Self::Rlb => "rlb",
+            Self::Hlt => "hlt",
+        }
+    }
 }
 impl TryFrom<i64> for Opcode {
     type Error = String;
@@ -85,6 +108,27 @@ impl TryFrom<i64> for Opcode {
     }
 }
 
+/// Decodes the cell at `addr` as an instruction, returning its mnemonic and
+/// length. Returns None if the value is not a well-formed instruction (bad
+/// opcode or address modes) or if its arguments run past the end of `mem`.
+pub fn decode_instr(mem: &[i64], addr: usize) -> Option<(&'static str, usize)> {
+    let instr = *mem.get(addr)?;
+    let op = Opcode::try_from(instr).ok()?;
+    let size = op.size();
+    if addr + size > mem.len() || instr / 10i64.pow(size as u32 + 1) != 0 {
+        return None;
+    }
+    for idx in 0..size - 1 {
+        match (instr / 10i64.pow(idx as u32 + 2)) % 10 {
+            0 | 2 => {}
+            1 if !op.stores_to(idx) => {}
+            _ => return None,
+        }
+    }
+    Some((op.mnemonic(), size))
+}
+
+#[derive(Clone)]
 pub struct IntcodeVM {
     pub pc: usize,
     pub mem: Vec<i64>,
@@ -234,35 +278,23 @@ impl IntcodeVM {
         }
     }
 
-    pub fn run_interactive<F>(&mut self, non_ascii_output: &mut F) -> Result<(), RunErr>
+    pub fn run_interactive<R, W, F>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+        non_ascii_output: &mut F,
+    ) -> Result<(), RunErr>
     where
+        R: BufRead,
+        W: Write,
         F: FnMut(i64),
     {
-        let mut output = |c| {
-            if c < 128 {
-                print!("{}", c as u8 as char);
-            } else {
-                non_ascii_output(c);
-            }
-        };
-        loop {
-            match self.step(&mut || None, &mut output) {
-                StepResult::Ok => continue,
-                StepResult::Halt => return Ok(()),
-                StepResult::InputNeeded => {}
-                StepResult::InvalidInstr(err) => return Err(RunErr::InvalidInstr(err)),
-            }
-            let mut buffer = String::new();
-            std::io::stdin().read_line(&mut buffer).unwrap();
-            buffer
-                .chars()
-                .for_each(|c| self.input_queue.push_back(c as u8 as i64));
-        }
+        Session::new().run(self, input, output, non_ascii_output)
     }
 
-    pub fn ascii_input(&mut self, input: &str) {
-        input
-            .chars()
-            .for_each(|c| self.input_queue.push_back(c as u8 as i64));
+    /// Queues text as input, or queues nothing if any of it is not 7-bit ASCII.
+    pub fn ascii_input(&mut self, input: &str) -> Result<(), RunErr> {
+        self.input_queue.extend(ascii::encode(input)?);
+        Ok(())
     }
 }
@@ -0,0 +1,218 @@
+use crate::ascii::{AsciiDecoder, AsciiOutput};
+use crate::intcode::{IntcodeVM, RunErr, StepResult};
+use crate::memdiff::hexdump;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+use std::vec::Vec;
+
+fn io_err(err: std::io::Error) -> RunErr {
+    RunErr::Io(err.to_string())
+}
+
+// Everything `:load` puts back, so that `:undo` and `:history` carry on
+// from the saved point rather than from wherever the session got to since
+#[derive(Clone)]
+struct Save {
+    vm: IntcodeVM,
+    history: Vec<String>,
+    undo: Vec<IntcodeVM>,
+}
+
+/// State kept across an interactive run: the command history, undo
+/// snapshots, named saves and an optional transcript of everything typed.
+///
+/// Lines starting with `:` are handled by the session instead of being
+/// sent to the program:
+///
+/// - `:save [name]` / `:load [name]` snapshot and restore the VM, along
+///   with the history and undo steps that led to it
+/// - `:undo` rewinds to before the last command
+/// - `:history` lists the commands sent so far
+/// - `:dump` shows the VM registers and memory
+#[derive(Default)]
+pub struct Session {
+    pub history: Vec<String>,
+    undo: Vec<IntcodeVM>,
+    saves: HashMap<String, Save>,
+    transcript: Option<Box<dyn Write>>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records every line read (meta-commands included) to `path`, so that
+    /// feeding the file back as input replays the session.
+    pub fn with_transcript(path: &Path) -> Result<Self, RunErr> {
+        let file = File::create(path).map_err(io_err)?;
+        Ok(Self {
+            transcript: Some(Box::new(BufWriter::new(file))),
+            ..Self::default()
+        })
+    }
+
+    pub fn run<R, W, F>(
+        &mut self,
+        vm: &mut IntcodeVM,
+        input: &mut R,
+        output: &mut W,
+        non_ascii_output: &mut F,
+    ) -> Result<(), RunErr>
+    where
+        R: BufRead,
+        W: Write,
+        F: FnMut(i64),
+    {
+        let mut decoder = AsciiDecoder::new();
+        loop {
+            let result = vm.step(&mut || None, &mut |v| decoder.push(v));
+            if result != StepResult::Ok {
+                decoder.flush();
+            }
+            for out in decoder.take() {
+                match out {
+                    AsciiOutput::Line(line) => writeln!(output, "{line}").map_err(io_err)?,
+                    AsciiOutput::Value(v) => non_ascii_output(v),
+                }
+            }
+            match result {
+                StepResult::Ok => continue,
+                StepResult::Halt => return Ok(()),
+                StepResult::InputNeeded => {}
+                StepResult::InvalidInstr(err) => return Err(RunErr::InvalidInstr(err)),
+            }
+            while vm.input_queue.is_empty() {
+                output.flush().map_err(io_err)?;
+                let mut buffer = String::new();
+                if input.read_line(&mut buffer).map_err(io_err)? == 0 {
+                    return Err(RunErr::EndOfInput);
+                }
+                if let Some(transcript) = self.transcript.as_mut() {
+                    transcript.write_all(buffer.as_bytes()).map_err(io_err)?;
+                    transcript.flush().map_err(io_err)?;
+                }
+                let line = buffer.trim_end_matches(['\r', '\n']);
+                if let Some(cmd) = line.strip_prefix(':') {
+                    self.meta_command(cmd, vm, output)?;
+                    continue;
+                }
+                let snapshot = vm.clone();
+                match vm.ascii_input(line) {
+                    Ok(_) => {
+                        vm.input_queue.push_back(10);
+                        self.undo.push(snapshot);
+                        self.history.push(line.to_owned());
+                    }
+                    Err(RunErr::InvalidInput(msg)) => writeln!(output, "{msg}").map_err(io_err)?,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+
+    fn meta_command<W: Write>(
+        &mut self,
+        cmd: &str,
+        vm: &mut IntcodeVM,
+        output: &mut W,
+    ) -> Result<(), RunErr> {
+        let (cmd, arg) = cmd.split_once(' ').unwrap_or((cmd, ""));
+        let arg = arg.trim();
+        match cmd {
+            "save" => {
+                let save = Save {
+                    vm: vm.clone(),
+                    history: self.history.clone(),
+                    undo: self.undo.clone(),
+                };
+                self.saves.insert(arg.to_owned(), save);
+                writeln!(output, "saved {arg:?}").map_err(io_err)
+            }
+            "load" => match self.saves.get(arg).cloned() {
+                Some(save) => {
+                    *vm = save.vm;
+                    self.history = save.history;
+                    self.undo = save.undo;
+                    writeln!(output, "loaded {arg:?}").map_err(io_err)
+                }
+                None => writeln!(output, "no save named {arg:?}").map_err(io_err),
+            },
+            "undo" => match self.undo.pop() {
+                Some(prev) => {
+                    *vm = prev;
+                    let cmd = self.history.pop().unwrap_or_default();
+                    writeln!(output, "undid {cmd:?}").map_err(io_err)
+                }
+                None => writeln!(output, "nothing to undo").map_err(io_err),
+            },
+            "history" => self
+                .history
+                .iter()
+                .enumerate()
+                .try_for_each(|(idx, cmd)| writeln!(output, "{idx:4} {cmd}"))
+                .map_err(io_err),
+            "dump" => {
+                writeln!(
+                    output,
+                    "pc={} relbase={} mem={}",
+                    vm.pc,
+                    vm.relbase,
+                    vm.mem.len()
+                )
+                .map_err(io_err)?;
+                write!(output, "{}", hexdump(&vm.mem, 8)).map_err(io_err)
+            }
+            _ => writeln!(output, "unknown command :{cmd}").map_err(io_err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::ProgMem;
+
+    // prints "?", then echoes one line back, forever
+    const ECHO: &str = "104,63,104,10,3,100,4,100,1008,100,10,101,1006,101,4,1105,1,0";
+
+    fn run(session: &mut Session, script: &str) -> (Result<(), RunErr>, String) {
+        let mut vm = IntcodeVM::with_mem(&ECHO.parse::<ProgMem>().unwrap());
+        let mut output = Vec::new();
+        let result = session.run(&mut vm, &mut script.as_bytes(), &mut output, &mut |_| {});
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn session_test() {
+        let mut session = Session::new();
+        let (result, output) = run(&mut session, "one\ntwo\n:undo\nthree\n");
+        assert_eq!(result, Err(RunErr::EndOfInput));
+        assert_eq!(output, "?\none\n?\ntwo\n?\nundid \"two\"\nthree\n?\n");
+        assert_eq!(session.history, ["one", "three"]);
+
+        let mut session = Session::new();
+        let (_, output) = run(&mut session, ":save a\nx\n:load a\n:load b\n:bogus\n");
+        assert_eq!(
+            output,
+            "?\nsaved \"a\"\nx\n?\nloaded \"a\"\nno save named \"b\"\nunknown command :bogus\n"
+        );
+
+        // loading rewinds the history and undo steps too
+        let mut session = Session::new();
+        let (_, output) = run(&mut session, "a\n:save s\nb\nc\n:load s\n:undo\n:undo\n");
+        assert!(output.ends_with("loaded \"s\"\nundid \"a\"\nnothing to undo\n"));
+        let mut session = Session::new();
+        let (result, _) = run(&mut session, "a\n:save s\nb\n:load s\nd\n");
+        assert_eq!(result, Err(RunErr::EndOfInput));
+        assert_eq!(session.history, ["a", "d"]);
+
+        let mut session = Session::new();
+        let (_, output) = run(&mut session, "caf\u{e9}\nok\n");
+        assert!(output.starts_with("?\nnon-ASCII"));
+        assert!(output.ends_with("ok\n?\n"));
+        assert_eq!(session.history, ["ok"]);
+    }
+}
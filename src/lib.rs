@@ -1 +1,11 @@
+pub mod amplifier;
+pub mod ascii;
+pub mod batch;
+pub mod heading;
 pub mod intcode;
+pub mod interactive;
+pub mod memdiff;
+pub mod nbody;
+pub mod ocr;
+pub mod pnm;
+pub mod progfile;
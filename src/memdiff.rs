@@ -0,0 +1,217 @@
+use crate::intcode::{decode_instr, IntcodeVM, ProgMem};
+use std::fmt;
+use std::vec::Vec;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CellKind {
+    Code,
+    Data,
+}
+
+/// Marks the cells of `mem` that hold instructions reachable from address 0.
+///
+/// This follows fall-through and jumps with immediate targets, so code only
+/// reached through computed jumps (such as returns via the relative base) is
+/// reported as data.
+pub fn code_cells(mem: &[i64]) -> Vec<bool> {
+    let mut code = vec![false; mem.len()];
+    let mut pending = vec![0];
+    while let Some(mut addr) = pending.pop() {
+        while let Some((mnemonic, size)) = decode_instr(mem, addr) {
+            if code[addr] {
+                break;
+            }
+            code[addr..addr + size].iter_mut().for_each(|c| *c = true);
+            let instr = mem[addr];
+            let fallthrough = match mnemonic {
+                "hlt" => false,
+                "jnz" | "jz" => {
+                    if (instr / 1000) % 10 == 1 && mem[addr + 2] >= 0 {
+                        pending.push(mem[addr + 2] as usize);
+                    }
+                    // with an immediate condition, the branch is either always or never taken
+                    let cond_immediate = (instr / 100) % 10 == 1;
+                    !(cond_immediate && (mem[addr + 1] != 0) == (mnemonic == "jnz"))
+                }
+                _ => true,
+            };
+            if !fallthrough {
+                break;
+            }
+            addr += size;
+        }
+    }
+    code
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangedRange {
+    pub start: usize,
+    pub kind: CellKind,
+    pub before: Vec<i64>,
+    pub after: Vec<i64>,
+}
+
+impl ChangedRange {
+    pub fn end(&self) -> usize {
+        self.start + self.before.len()
+    }
+}
+
+impl fmt::Display for ChangedRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            CellKind::Code => "code",
+            CellKind::Data => "data",
+        };
+        write!(
+            f,
+            "{:6}..{:<6} {kind}: {:?} -> {:?}",
+            self.start,
+            self.end(),
+            self.before,
+            self.after
+        )
+    }
+}
+
+/// Lists the runs of consecutive cells that differ between `before` and
+/// `after`, split where the classification (from `before`) switches between
+/// code and data. Cells past the end of the shorter memory count as 0.
+pub fn diff(before: &[i64], after: &[i64]) -> Vec<ChangedRange> {
+    let code = code_cells(before);
+    let mut ranges: Vec<ChangedRange> = Vec::new();
+    for addr in 0..before.len().max(after.len()) {
+        let b = before.get(addr).copied().unwrap_or(0);
+        let a = after.get(addr).copied().unwrap_or(0);
+        if a == b {
+            continue;
+        }
+        let kind = if code.get(addr).copied().unwrap_or(false) {
+            CellKind::Code
+        } else {
+            CellKind::Data
+        };
+        match ranges.last_mut() {
+            Some(r) if r.end() == addr && r.kind == kind => {
+                r.before.push(b);
+                r.after.push(a);
+            }
+            _ => ranges.push(ChangedRange {
+                start: addr,
+                kind,
+                before: vec![b],
+                after: vec![a],
+            }),
+        }
+    }
+    ranges
+}
+
+pub fn diff_vms(before: &IntcodeVM, after: &IntcodeVM) -> Vec<ChangedRange> {
+    diff(&before.mem, &after.mem)
+}
+
+pub fn diff_from_prog(prog: &ProgMem, vm: &IntcodeVM) -> Vec<ChangedRange> {
+    diff(&prog.0, &vm.mem)
+}
+
+// Negative values get a sign rather than their two's complement.
+fn hex(v: i64) -> String {
+    if v < 0 {
+        format!("-{:x}", v.unsigned_abs())
+    } else {
+        format!("{v:x}")
+    }
+}
+
+/// Renders memory in hex, `width` cells per row (at least one), each row
+/// prefixed by its address and followed by a sidebar showing the cells that
+/// are printable ASCII.
+pub fn hexdump(mem: &[i64], width: usize) -> String {
+    let width = width.max(1);
+    let cellw = mem.iter().map(|v| hex(*v).len()).max().unwrap_or(1);
+    mem.chunks(width)
+        .enumerate()
+        .map(|(row, vals)| {
+            let cells: Vec<String> = vals
+                .iter()
+                .map(|v| format!("{:>cellw$}", hex(*v)))
+                .collect();
+            let ascii: String = vals
+                .iter()
+                .map(|v| match *v {
+                    32..=126 => *v as u8 as char,
+                    _ => '.',
+                })
+                .collect();
+            format!(
+                "{:06x}: {:<rowlen$} |{ascii}|\n",
+                row * width,
+                cells.join(" "),
+                rowlen = width * (cellw + 1) - 1,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_test() {
+        let prog: ProgMem = "1,9,10,3,2,3,11,0,99,30,40,50".parse().unwrap();
+        let code: Vec<usize> = code_cells(&prog.0)
+            .iter()
+            .enumerate()
+            .filter_map(|(addr, c)| c.then_some(addr))
+            .collect();
+        assert_eq!(code, (0..=8).collect::<Vec<_>>());
+
+        let mut vm = IntcodeVM::with_mem(&prog);
+        vm.run().unwrap();
+        assert_eq!(
+            diff_from_prog(&prog, &vm),
+            [
+                ChangedRange {
+                    start: 0,
+                    kind: CellKind::Code,
+                    before: vec![1],
+                    after: vec![3500],
+                },
+                ChangedRange {
+                    start: 3,
+                    kind: CellKind::Code,
+                    before: vec![3],
+                    after: vec![70],
+                },
+            ]
+        );
+        assert_eq!(
+            diff(&[99, 2, 3, 4], &[99, 2, 3, 5, 6]),
+            [ChangedRange {
+                start: 3,
+                kind: CellKind::Data,
+                before: vec![4, 0],
+                after: vec![5, 6],
+            }]
+        );
+        // unconditional jump over data
+        let code = code_cells(&[1105, 1, 4, 7, 99]);
+        assert_eq!(code, [true, true, true, false, true]);
+    }
+
+    #[test]
+    fn hexdump_test() {
+        assert_eq!(
+            hexdump(&[72, 105, 10, 1000, 33], 4),
+            "000000:  48  69   a 3e8 |Hi..|\n\
+             000004:  21             |!|\n"
+        );
+        // addresses are hex too, and negative values keep their sign
+        let dump = hexdump(&[-1; 17], 16);
+        assert!(dump.contains("\n000010: -1 "));
+        assert_eq!(hexdump(&[99, 0], 0), "000000: 63 |c|\n000001:  0 |.|\n");
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::vec::Vec;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Body<const D: usize> {
+    pub pos: [i64; D],
+    pub vel: [i64; D],
+}
+
+impl<const D: usize> Body<D> {
+    pub fn new(pos: [i64; D]) -> Self {
+        Self { pos, vel: [0; D] }
+    }
+
+    /// Potential (sum of absolute position) times kinetic (sum of absolute
+    /// velocity) energy.
+    pub fn energy(&self) -> i64 {
+        let pot: i64 = self.pos.iter().map(|v| v.abs()).sum();
+        let kin: i64 = self.vel.iter().map(|v| v.abs()).sum();
+        pot * kin
+    }
+}
+
+/// How bodies accelerate. Each axis is handled on its own: `pull(a, b)` is
+/// the change in velocity of a body at `a` due to one at `b`, and velocity
+/// is then clamped to `speed_limit`, if there is one.
+#[derive(Copy, Clone, Debug)]
+pub struct Gravity {
+    pub pull: fn(i64, i64) -> i64,
+    pub speed_limit: Option<i64>,
+}
+
+impl Default for Gravity {
+    /// Each pair of bodies pulls one unit towards each other.
+    fn default() -> Self {
+        Self {
+            pull: |a, b| (b - a).signum(),
+            speed_limit: None,
+        }
+    }
+}
+
+impl Gravity {
+    // Steps the bodies along one axis, given as (position, velocity) pairs.
+    fn step_axis(&self, axis: &mut [(i64, i64)]) {
+        let dv: Vec<i64> = axis
+            .iter()
+            .map(|(a, _)| axis.iter().map(|(b, _)| (self.pull)(*a, *b)).sum())
+            .collect();
+        for ((pos, vel), dv) in axis.iter_mut().zip(dv) {
+            *vel += dv;
+            if let Some(limit) = self.speed_limit {
+                *vel = (*vel).clamp(-limit, limit);
+            }
+            *pos += *vel;
+        }
+    }
+}
+
+/// Where a sequence of states starts repeating (`start`), and how often.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SeriesFormat {
+    Csv,
+    JsonLines,
+}
+
+impl FromStr for SeriesFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(SeriesFormat::Csv),
+            "jsonl" => Ok(SeriesFormat::JsonLines),
+            _ => Err(format!("Unknown format {s:?}")),
+        }
+    }
+}
+
+fn join(vals: &[i64]) -> String {
+    vals.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Clone, Debug)]
+pub struct System<const D: usize> {
+    pub bodies: Vec<Body<D>>,
+    pub gravity: Gravity,
+    pub steps: usize,
+}
+
+impl<const D: usize> System<D> {
+    pub fn new(bodies: Vec<Body<D>>, gravity: Gravity) -> Self {
+        Self {
+            bodies,
+            gravity,
+            steps: 0,
+        }
+    }
+
+    fn axis(&self, d: usize) -> Vec<(i64, i64)> {
+        self.bodies.iter().map(|b| (b.pos[d], b.vel[d])).collect()
+    }
+
+    pub fn step(&mut self) {
+        for d in 0..D {
+            let mut axis = self.axis(d);
+            self.gravity.step_axis(&mut axis);
+            for (body, (pos, vel)) in self.bodies.iter_mut().zip(axis) {
+                body.pos[d] = pos;
+                body.vel[d] = vel;
+            }
+        }
+        self.steps += 1;
+    }
+
+    pub fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    pub fn energy(&self) -> i64 {
+        self.bodies.iter().map(|b| b.energy()).sum()
+    }
+
+    /// Finds the cycle that the motion along axis `d` falls into, using
+    /// Brent's algorithm so that it needn't come back to the current state.
+    /// Gives up after `limit` steps.
+    pub fn axis_cycle(&self, d: usize, limit: usize) -> Option<Cycle> {
+        // states are compared by hash first, as most comparisons differ
+        let hashed = |axis: Vec<(i64, i64)>| {
+            let mut h = DefaultHasher::new();
+            axis.hash(&mut h);
+            (h.finish(), axis)
+        };
+        let next = |(_, mut axis): (u64, Vec<(i64, i64)>)| {
+            self.gravity.step_axis(&mut axis);
+            hashed(axis)
+        };
+        let start = hashed(self.axis(d));
+
+        let mut power = 1;
+        let mut period = 1;
+        let mut tortoise = start.clone();
+        let mut hare = next(start.clone());
+        let mut taken = 1;
+        while tortoise != hare {
+            if taken >= limit {
+                return None;
+            }
+            if power == period {
+                tortoise = hare.clone();
+                power *= 2;
+                period = 0;
+            }
+            hare = next(hare);
+            period += 1;
+            taken += 1;
+        }
+
+        let mut tortoise = start.clone();
+        let mut hare = start;
+        for _ in 0..period {
+            hare = next(hare);
+        }
+        let mut mu = 0;
+        while tortoise != hare {
+            tortoise = next(tortoise);
+            hare = next(hare);
+            mu += 1;
+        }
+        Some(Cycle { start: mu, period })
+    }
+
+    /// Axes move independently, so the whole system repeats once every
+    /// axis is in its cycle, with the lowest common multiple of the periods.
+    pub fn cycle(&self, limit: usize) -> Option<Cycle> {
+        (0..D).map(|d| self.axis_cycle(d, limit)).try_fold(
+            Cycle {
+                start: 0,
+                period: 1,
+            },
+            |acc, c| {
+                let c = c?;
+                Some(Cycle {
+                    start: acc.start.max(c.start),
+                    period: num::integer::lcm(acc.period, c.period),
+                })
+            },
+        )
+    }
+
+    /// Writes one record per body for the current step.
+    pub fn write_records<W: Write>(&self, out: &mut W, format: SeriesFormat) -> io::Result<()> {
+        for (i, b) in self.bodies.iter().enumerate() {
+            match format {
+                SeriesFormat::Csv => writeln!(
+                    out,
+                    "{},{i},{},{},{}",
+                    self.steps,
+                    join(&b.pos),
+                    join(&b.vel),
+                    b.energy()
+                )?,
+                SeriesFormat::JsonLines => writeln!(
+                    out,
+                    "{{\"step\":{},\"body\":{i},\"pos\":[{}],\"vel\":[{}],\"energy\":{}}}",
+                    self.steps,
+                    join(&b.pos),
+                    join(&b.vel),
+                    b.energy()
+                )?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the state before and after each of the next `steps` steps,
+    /// leaving the system at the end.
+    pub fn export<W: Write>(
+        &mut self,
+        out: &mut W,
+        format: SeriesFormat,
+        steps: usize,
+    ) -> io::Result<()> {
+        if format == SeriesFormat::Csv {
+            let cols = |name: &str| -> String {
+                (0..D)
+                    .map(|d| format!("{name}{d}"))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            writeln!(out, "step,body,{},{},energy", cols("pos"), cols("vel"))?;
+        }
+        self.write_records(out, format)?;
+        for _ in 0..steps {
+            self.step();
+            self.write_records(out, format)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nbody_test() {
+        let bodies = [[-1, 0, 2], [2, -10, -7], [4, -8, 8], [3, 5, -1]];
+        let mut system = System::new(bodies.map(Body::new).to_vec(), Gravity::default());
+        assert_eq!(
+            system.cycle(10_000),
+            Some(Cycle {
+                start: 0,
+                period: 2772,
+            })
+        );
+        system.run(10);
+        assert_eq!(system.energy(), 179);
+        assert_eq!(system.bodies[0].pos, [2, 1, -3]);
+        assert_eq!(system.bodies[0].vel, [-3, -2, 1]);
+
+        // 2D, with more bodies
+        let bodies = [[0, 0], [5, 1], [-3, 4], [2, 2], [1, -1]];
+        let system = System::new(bodies.map(Body::new).to_vec(), Gravity::default());
+        let cycle = system.cycle(1_000_000).unwrap();
+        let mut a = system.clone();
+        a.run(cycle.period);
+        assert_eq!(a.bodies, system.bodies);
+
+        // the speed limit loses information, so the start isn't revisited
+        let gravity = Gravity {
+            pull: |a, b| 2 * (b - a).signum(),
+            speed_limit: Some(1),
+        };
+        let system = System::new(vec![Body::new([0]), Body::new([3])], gravity);
+        let cycle = system.cycle(1000).unwrap();
+        assert!(cycle.start > 0);
+        let mut a = system.clone();
+        a.run(cycle.start);
+        let mut b = a.clone();
+        b.run(cycle.period);
+        assert_eq!(a.bodies, b.bodies);
+        let mut c = system.clone();
+        c.run(cycle.start - 1);
+        let mut d = c.clone();
+        d.run(cycle.period);
+        assert_ne!(c.bodies, d.bodies);
+
+        // flying apart never repeats
+        let gravity = Gravity {
+            pull: |a, b| (a - b).signum(),
+            speed_limit: None,
+        };
+        let system = System::new(vec![Body::new([0]), Body::new([1])], gravity);
+        assert_eq!(system.cycle(1000), None);
+    }
+
+    #[test]
+    fn export_test() {
+        let mut system = System::new(
+            vec![Body::new([0, 1]), Body::new([2, 1])],
+            Gravity::default(),
+        );
+        let mut out = Vec::new();
+        system.export(&mut out, SeriesFormat::Csv, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "step,body,pos0,pos1,vel0,vel1,energy
+0,0,0,1,0,0,0
+0,1,2,1,0,0,0
+1,0,1,1,1,0,2
+1,1,1,1,-1,0,2
+"
+        );
+        let mut out = Vec::new();
+        system
+            .write_records(&mut out, "jsonl".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap().lines().next(),
+            Some(r#"{"step":1,"body":0,"pos":[1,1],"vel":[1,0],"energy":2}"#)
+        );
+    }
+}
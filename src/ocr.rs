@@ -0,0 +1,257 @@
+use std::fmt;
+use std::str::FromStr;
+use std::vec::Vec;
+
+// The block-letter font used by several puzzles. Glyphs are six pixels tall
+// and sit on a fixed pitch, which leaves a blank column after all but `Y`.
+const PITCH: usize = 5;
+const GLYPHS: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// A rectangular image of lit and unlit pixels.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Raster {
+    pub fn new(width: usize, pixels: Vec<bool>) -> Self {
+        let height = pixels.len().checked_div(width).unwrap_or(0);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Builds a raster from an image layer where 1 is lit, and anything
+    /// else (black or transparent) is not.
+    pub fn from_layer(layer: &[u8], width: usize) -> Self {
+        Self::new(width, layer.iter().map(|p| *p == 1).collect())
+    }
+
+    /// Builds the smallest raster containing all of the given lit points.
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = (i64, i64)>,
+    {
+        let points: Vec<(i64, i64)> = points.into_iter().collect();
+        if points.is_empty() {
+            return Self::new(0, Vec::new());
+        }
+        let xmin = points.iter().map(|p| p.0).min().unwrap();
+        let xmax = points.iter().map(|p| p.0).max().unwrap();
+        let ymin = points.iter().map(|p| p.1).min().unwrap();
+        let ymax = points.iter().map(|p| p.1).max().unwrap();
+        let width = (xmax - xmin + 1) as usize;
+        let height = (ymax - ymin + 1) as usize;
+        let mut pixels = vec![false; width * height];
+        for (x, y) in points {
+            pixels[(y - ymin) as usize * width + (x - xmin) as usize] = true;
+        }
+        Self::new(width, pixels)
+    }
+
+    pub fn pixels(&self) -> &[bool] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn render(&self, on: char, off: char) -> String {
+        (0..self.height)
+            .map(|y| {
+                let mut row: String = (0..self.width)
+                    .map(|x| if self.get(x, y) { on } else { off })
+                    .collect();
+                row.push('\n');
+                row
+            })
+            .collect()
+    }
+
+    fn column_blank(&self, x: usize) -> bool {
+        (0..self.height).all(|y| !self.get(x, y))
+    }
+
+    fn row_blank(&self, y: usize) -> bool {
+        (0..self.width).all(|x| !self.get(x, y))
+    }
+}
+
+impl FromStr for Raster {
+    type Err = String;
+    /// Parses rows of `#` (lit) and `.` (unlit).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = s.lines().collect();
+        let width = rows.first().map_or(0, |r| r.len());
+        if let Some(bad) = rows.iter().find(|r| r.len() != width) {
+            return Err(format!("row {bad:?} is not {width} wide"));
+        }
+        Ok(Self::new(
+            width,
+            rows.iter()
+                .flat_map(|r| r.chars())
+                .map(|c| c == '#')
+                .collect(),
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OcrError {
+    /// Index of the glyph within the text
+    pub index: usize,
+    /// Column of the raster where the glyph starts
+    pub column: usize,
+    /// The glyph drawn with `#` and `.`
+    pub glyph: String,
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unrecognized glyph #{} at column {}:\n{}",
+            self.index, self.column, self.glyph
+        )
+    }
+}
+
+// Draws a `width` x `rows` image as '#'/'.' text, one line per row,
+// with any blank columns at either edge trimmed off.
+fn trimmed_art<F>(width: usize, rows: usize, lit: F) -> String
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let used: Vec<usize> = (0..width)
+        .filter(|x| (0..rows).any(|y| lit(*x, y)))
+        .collect();
+    let (Some(first), Some(last)) = (used.first(), used.last()) else {
+        return String::new();
+    };
+    (0..rows)
+        .map(|y| {
+            let mut row: String = (*first..=*last)
+                .map(|x| if lit(x, y) { '#' } else { '.' })
+                .collect();
+            row.push('\n');
+            row
+        })
+        .collect()
+}
+
+/// Reads the block letters in `raster`. Blank margins are ignored, and
+/// glyphs are split wherever there is a fully blank column, or after
+/// `PITCH` columns where one glyph runs straight into the next.
+pub fn recognize(raster: &Raster) -> Result<String, OcrError> {
+    let Some(top) = (0..raster.height).find(|y| !raster.row_blank(*y)) else {
+        return Ok(String::new());
+    };
+    let bottom = (0..raster.height)
+        .rev()
+        .find(|y| !raster.row_blank(*y))
+        .unwrap();
+    let rows = bottom - top + 1;
+
+    let mut text = String::new();
+    let mut x = 0;
+    while x < raster.width {
+        if raster.column_blank(x) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < raster.width && x < start + PITCH && !raster.column_blank(x) {
+            x += 1;
+        }
+        let art = trimmed_art(x - start, rows, |gx, gy| raster.get(start + gx, top + gy));
+        let found = GLYPHS.iter().find(|(_, g)| {
+            trimmed_art(g[0].len(), g.len(), |gx, gy| g[gy].as_bytes()[gx] == b'#') == art
+        });
+        match found {
+            Some((c, _)) => text.push(*c),
+            None => {
+                return Err(OcrError {
+                    index: text.len(),
+                    column: start,
+                    glyph: art,
+                })
+            }
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ocr_test() {
+        let raster: Raster = "\
+.#..#.###...##..####.#...#..###.
+.#..#..#...#..#.#....#...#...#..
+.####..#...#....###...#.#....#..
+.#..#..#...#....#......#.....#..
+.#..#..#...#..#.#......#.....#..
+.#..#.###...##..####...#....###."
+            .parse()
+            .unwrap();
+        assert_eq!(recognize(&raster), Ok("HICEYI".to_string()));
+
+        // nothing separates a Y from the letter after it
+        let raster: Raster = "\
+#...##..#.
+#...##..#.
+.#.#.####.
+..#..#..#.
+..#..#..#.
+..#..#..#."
+            .parse()
+            .unwrap();
+        assert_eq!(recognize(&raster), Ok("YH".to_string()));
+
+        let layer = [
+            0, 1, 1, 0, 2, 1, 0, 0, 1, 2, 1, 0, 0, 1, 2, 1, 0, 0, 1, 2, 1, 0, 0, 1, 2, 0, 1, 1, 0,
+            2,
+        ];
+        let raster = Raster::from_layer(&layer, 5);
+        assert_eq!(recognize(&raster), Ok("O".to_string()));
+
+        let raster = Raster::from_points([(10, 3), (11, 4), (12, 5)]);
+        assert_eq!((raster.width, raster.height), (3, 3));
+        assert_eq!(raster.render('#', '.'), "#..\n.#.\n..#\n");
+        assert_eq!(
+            recognize(&raster),
+            Err(OcrError {
+                index: 0,
+                column: 0,
+                glyph: "#..\n.#.\n..#\n".to_string(),
+            })
+        );
+        assert_eq!(recognize(&Raster::new(0, vec![])), Ok(String::new()));
+    }
+}
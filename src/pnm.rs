@@ -0,0 +1,110 @@
+use crate::ocr::Raster;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::vec::Vec;
+
+// Netpbm allows several images back to back in one file, which is how
+// animations are written here; tools like ImageMagick read them as frames.
+
+/// Writes a binary (P4) bitmap. Lit pixels are drawn black.
+pub fn write_pbm<W: Write>(out: &mut W, raster: &Raster) -> io::Result<()> {
+    write!(out, "P4\n{} {}\n", raster.width, raster.height)?;
+    for y in 0..raster.height {
+        let mut row = vec![0u8; raster.width.div_ceil(8)];
+        for x in (0..raster.width).filter(|x| raster.get(*x, y)) {
+            row[x / 8] |= 0x80 >> (x % 8);
+        }
+        out.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Writes a binary (P5) greymap with 8-bit samples.
+pub fn write_pgm<W: Write>(out: &mut W, width: usize, pixels: &[u8]) -> io::Result<()> {
+    let height = pixels.len().checked_div(width).unwrap_or(0);
+    write!(out, "P5\n{width} {height}\n255\n")?;
+    out.write_all(&pixels[..width * height])
+}
+
+/// Writes a binary (P6) pixmap with 8-bit RGB samples.
+pub fn write_ppm<W: Write>(out: &mut W, width: usize, pixels: &[[u8; 3]]) -> io::Result<()> {
+    let height = pixels.len().checked_div(width).unwrap_or(0);
+    write!(out, "P6\n{width} {height}\n255\n")?;
+    let bytes: Vec<u8> = pixels[..width * height].iter().flatten().copied().collect();
+    out.write_all(&bytes)
+}
+
+/// Writes `frames`, each `scale` times larger, one after another as a
+/// single animated pixmap.
+pub fn write_ppm_frames<W, I>(out: &mut W, width: usize, frames: I, scale: usize) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = Vec<[u8; 3]>>,
+{
+    frames
+        .into_iter()
+        .try_for_each(|pixels| write_ppm(out, width * scale, &upscale(width, &pixels, scale)))
+}
+
+/// Picks every `every`th of `count` frames to keep an animation short,
+/// always ending on the last one.
+pub fn sampled_frames(count: usize, every: usize) -> impl Iterator<Item = usize> {
+    let every = every.max(1);
+    let last = count.checked_sub(1).filter(|n| !n.is_multiple_of(every));
+    (0..count).step_by(every).chain(last)
+}
+
+/// Creates `name` in `dir`, for exports that save several files together.
+pub fn create_in(dir: &Path, name: &str) -> io::Result<BufWriter<File>> {
+    File::create(dir.join(name)).map(BufWriter::new)
+}
+
+/// Enlarges an image so that each pixel becomes a `scale` x `scale` block,
+/// since puzzle images are often only a few dozen pixels across.
+pub fn upscale<T: Copy>(width: usize, pixels: &[T], scale: usize) -> Vec<T> {
+    pixels
+        .chunks(width.max(1))
+        .flat_map(|row| {
+            let row: Vec<T> = row
+                .iter()
+                .flat_map(|p| std::iter::repeat_n(*p, scale))
+                .collect();
+            std::iter::repeat_n(row, scale).flatten()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pnm_test() {
+        let raster: Raster = "#........#\n.#........".parse().unwrap();
+        let mut out = Vec::new();
+        write_pbm(&mut out, &raster).unwrap();
+        assert_eq!(out, b"P4\n10 2\n\x80\x40\x40\x00");
+
+        let mut out = Vec::new();
+        write_pgm(&mut out, 2, &[0, 128, 255, 7]).unwrap();
+        assert_eq!(out, b"P5\n2 2\n255\n\x00\x80\xff\x07");
+
+        let mut out = Vec::new();
+        write_ppm(&mut out, 1, &[[1, 2, 3], [4, 5, 6]]).unwrap();
+        assert_eq!(out, b"P6\n1 2\n255\n\x01\x02\x03\x04\x05\x06");
+
+        assert_eq!(
+            upscale(2, &[1, 2, 3, 4], 2),
+            [1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]
+        );
+
+        let mut out = Vec::new();
+        write_ppm_frames(&mut out, 1, [vec![[1, 2, 3]], vec![[4, 5, 6]]], 1).unwrap();
+        assert_eq!(out, b"P6\n1 1\n255\n\x01\x02\x03P6\n1 1\n255\n\x04\x05\x06");
+        assert!(sampled_frames(7, 3).eq([0, 3, 6]));
+        assert!(sampled_frames(8, 3).eq([0, 3, 6, 7]));
+        assert!(sampled_frames(2, 0).eq([0, 1]));
+        assert_eq!(sampled_frames(0, 3).count(), 0);
+    }
+}
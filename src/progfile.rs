@@ -0,0 +1,227 @@
+use crate::intcode::{decode_instr, ProgMem};
+use std::fmt::Write;
+use std::str::FromStr;
+use std::vec::Vec;
+
+const BINARY_MAGIC: &[u8] = b"\0ICB";
+const VARINT_MAGIC: &[u8] = b"\0ICV";
+
+/// On-disk representations of an Intcode program.
+///
+/// - `Text`: the puzzle's own single line of comma-separated values
+/// - `Annotated`: text spread over any number of lines; values may be separated
+///   by commas or whitespace, and `#` or `;` start a comment
+/// - `Binary`: a magic header followed by each value as a little-endian `i64`
+/// - `Varint`: a magic header followed by each value zigzag/LEB128 encoded
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    Text,
+    Annotated,
+    Binary,
+    Varint,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "annotated" => Ok(Self::Annotated),
+            "binary" => Ok(Self::Binary),
+            "varint" => Ok(Self::Varint),
+            _ => Err(format!("unknown format {s:?}")),
+        }
+    }
+}
+
+impl Format {
+    /// Picks the format of `bytes` by its magic header. Anything without one
+    /// is treated as annotated text, which also accepts the plain text format.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(BINARY_MAGIC) {
+            Self::Binary
+        } else if bytes.starts_with(VARINT_MAGIC) {
+            Self::Varint
+        } else {
+            Self::Annotated
+        }
+    }
+}
+
+impl ProgMem {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        match Format::detect(bytes) {
+            Format::Binary => Self::from_binary(bytes),
+            Format::Varint => Self::from_varint(bytes),
+            Format::Text | Format::Annotated => {
+                let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                Self::from_annotated(text)
+            }
+        }
+    }
+
+    pub fn to_bytes(&self, format: Format) -> Vec<u8> {
+        match format {
+            Format::Text => self.to_text().into_bytes(),
+            Format::Annotated => self.to_annotated().into_bytes(),
+            Format::Binary => self.to_binary(),
+            Format::Varint => self.to_varint(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let vals: Vec<String> = self.0.iter().map(|v| v.to_string()).collect();
+        format!("{}\n", vals.join(","))
+    }
+
+    /// Writes one instruction per line, with its address and mnemonic in a
+    /// comment. Cells that don't decode as an instruction are written singly
+    /// and marked as data. The decoding is a linear sweep, so data that
+    /// happens to look like an instruction will be labelled as one.
+    pub fn to_annotated(&self) -> String {
+        let mut out = String::new();
+        let mut addr = 0;
+        while addr < self.0.len() {
+            let (label, size) = decode_instr(&self.0, addr).unwrap_or(("data", 1));
+            let vals: Vec<String> = self.0[addr..addr + size]
+                .iter()
+                .map(|v| v.to_string())
+                .collect();
+            writeln!(out, "{:<40} # {addr:5}: {label}", vals.join(", ") + ",").unwrap();
+            addr += size;
+        }
+        out
+    }
+
+    pub fn from_annotated(s: &str) -> Result<Self, String> {
+        let mut mem = Vec::new();
+        for (lineno, line) in s.lines().enumerate() {
+            let line = line.split(['#', ';']).next().unwrap();
+            for tok in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                if tok.is_empty() {
+                    continue;
+                }
+                let val = tok
+                    .parse::<i64>()
+                    .map_err(|e| format!("line {}: {tok:?}: {e}", lineno + 1))?;
+                mem.push(val);
+            }
+        }
+        Ok(Self(mem))
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = BINARY_MAGIC.to_vec();
+        self.0
+            .iter()
+            .for_each(|v| out.extend_from_slice(&v.to_le_bytes()));
+        out
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, String> {
+        let body = bytes
+            .strip_prefix(BINARY_MAGIC)
+            .ok_or("missing binary header")?;
+        if body.len() % 8 != 0 {
+            return Err(format!("binary length {} not a multiple of 8", body.len()));
+        }
+        Ok(Self(
+            body.chunks(8)
+                .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        ))
+    }
+
+    pub fn to_varint(&self) -> Vec<u8> {
+        let mut out = VARINT_MAGIC.to_vec();
+        for v in &self.0 {
+            let mut zz = ((v << 1) ^ (v >> 63)) as u64;
+            while zz >= 0x80 {
+                out.push((zz as u8) | 0x80);
+                zz >>= 7;
+            }
+            out.push(zz as u8);
+        }
+        out
+    }
+
+    pub fn from_varint(bytes: &[u8]) -> Result<Self, String> {
+        let body = bytes
+            .strip_prefix(VARINT_MAGIC)
+            .ok_or("missing varint header")?;
+        let mut mem = Vec::new();
+        let mut zz: u64 = 0;
+        let mut shift = 0;
+        for (idx, b) in body.iter().enumerate() {
+            // the 10th byte holds only the top bit of a u64
+            if shift > 63 || (shift == 63 && b & 0x7f > 1) {
+                return Err(format!("varint too long at byte {idx}"));
+            }
+            zz |= ((b & 0x7f) as u64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                mem.push((zz >> 1) as i64 ^ -((zz & 1) as i64));
+                zz = 0;
+                shift = 0;
+            }
+        }
+        if shift != 0 {
+            return Err("truncated varint at end of input".into());
+        }
+        Ok(Self(mem))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progfile_test() {
+        let prog = ProgMem(vec![1002, 4, 3, 4, 33, -1, 0, i64::MAX, i64::MIN, 99]);
+        for format in [
+            Format::Text,
+            Format::Annotated,
+            Format::Binary,
+            Format::Varint,
+        ] {
+            let bytes = prog.to_bytes(format);
+            assert_eq!(ProgMem::from_bytes(&bytes).unwrap().0, prog.0);
+        }
+        assert_eq!(prog.to_binary().len(), 4 + 8 * 10);
+        assert_eq!(ProgMem(vec![1, -1, 63, -64]).to_varint().len(), 4 + 4);
+
+        assert_eq!(
+            ProgMem(vec![1002, 4, 3, 4, 33]).to_annotated(),
+            "1002, 4, 3, 4,                           #     0: mul\n\
+             33,                                      #     4: data\n"
+        );
+    }
+
+    #[test]
+    fn annotated_parse_test() {
+        let prog = ProgMem::from_annotated(
+            "# header comment
+             1002,4,3,4   ; multiply
+             33 99\n",
+        )
+        .unwrap();
+        assert_eq!(prog.0, [1002, 4, 3, 4, 33, 99]);
+        assert_eq!(
+            ProgMem::from_annotated("1,2\n3,x\n"),
+            Err("line 2: \"x\": invalid digit found in string".into())
+        );
+        assert!(ProgMem::from_binary(b"\0ICB1234").is_err());
+        assert!(ProgMem::from_varint(b"\0ICV\x80").is_err());
+
+        let min = ProgMem(vec![i64::MIN, i64::MAX]);
+        assert_eq!(ProgMem::from_varint(&min.to_varint()), Ok(min));
+        let mut wide = b"\0ICV".to_vec();
+        wide.extend([0xff; 9]);
+        wide.push(0x02);
+        assert_eq!(
+            ProgMem::from_varint(&wide),
+            Err("varint too long at byte 9".into())
+        );
+    }
+}